@@ -1,26 +1,56 @@
+use crate::token::Span;
 
+/// The root of the AST: every function of a translation unit, in source
+/// order.
+#[derive(Debug)]
+pub struct Program {
+    pub functions: Vec<Function>
+}
+
+impl Program {
+    pub fn new(functions: Vec<Function>) -> Self {
+        Program {
+            functions
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Function {
     pub return_type: FunctionType,
     pub name: String,
-    pub block: Block
+    pub params: Vec<Param>,
+    pub block: Block,
+    /// span of `name`
+    pub span: Span
 }
 
 impl Function {
     pub fn new(
         return_type: FunctionType,
         name: String,
-        block: Block
+        params: Vec<Param>,
+        block: Block,
+        span: Span
     ) -> Self {
-        return Function {
+        Function {
             return_type,
             name,
-            block
+            params,
+            block,
+            span
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Param {
+    pub ident_type: IdentType,
+    pub name: String,
+    /// span of `name`
+    pub span: Span
+}
+
 #[derive(Debug)]
 pub enum FunctionType {
     Int,
@@ -34,7 +64,7 @@ pub struct Block {
 impl Block {
     pub fn new(stmts: Vec<Stmt>) -> Self {
         Block {
-            stmts: stmts
+            stmts
         }
     }
 }
@@ -53,13 +83,19 @@ pub enum Stmt {
     },
     AssignmentStmt {
         lval: String,
-        rval: Expr
+        rval: Expr,
+        /// span of `lval`
+        span: Span
     },
     DeclareStmt {
         ident_type: IdentType,
         ident: String,
-        rval: Option<Expr>
-    }
+        rval: Option<Expr>,
+        /// span of `ident`
+        span: Span
+    },
+    /// Placeholder for a statement that failed to parse.
+    Error(Span)
 }
 
 #[derive(Debug)]
@@ -69,11 +105,37 @@ pub enum IdentType {
 
 #[derive(Debug)]
 pub enum Expr {
-    Number(i32),
-    Var(String),
+    Number(i32, Span),
+    Var(String, Span),
     BinaryExpr {
         op: String,
         lhs: Box<Expr>,
-        rhs: Box<Expr>
+        rhs: Box<Expr>,
+        span: Span
+    },
+    UnaryExpr {
+        op: String,
+        operand: Box<Expr>,
+        span: Span
+    },
+    Call {
+        name: String,
+        args: Vec<Expr>,
+        span: Span
+    },
+    /// Placeholder for an expression that failed to parse.
+    Error(Span)
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Number(_, span) => *span,
+            Expr::Var(_, span) => *span,
+            Expr::BinaryExpr { span, .. } => *span,
+            Expr::UnaryExpr { span, .. } => *span,
+            Expr::Call { span, .. } => *span,
+            Expr::Error(span) => *span,
+        }
     }
 }
@@ -0,0 +1,276 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::ir::{Opcode, Operand, Quadruple};
+
+pub type BlockId = usize;
+
+/// A maximal run of quadruples entered only at its first one and left only
+/// after its last one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    /// index of the first quadruple
+    pub start: usize,
+    /// index one past the last quadruple
+    pub end: usize,
+    pub preds: Vec<BlockId>,
+    /// the fall-through successor comes before the jump target
+    pub succs: Vec<BlockId>,
+}
+
+impl BasicBlock {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// Control-flow graph over a list of quadruples, as produced by
+/// `CodeGenerator`. Blocks are numbered in quadruple order.
+///
+/// Every function is a separate subgraph whose entry block starts with its
+/// `func`; calls do not add edges. Leaving a function, by `return`, by
+/// running into the next `func` or by jumping past the last quadruple,
+/// leaves the graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    /// block containing each quadruple
+    pub block_of: Vec<BlockId>,
+    /// blocks of each function, the first one being its entry
+    pub functions: Vec<Range<BlockId>>,
+}
+
+impl Cfg {
+    pub fn build(quads: &[Quadruple]) -> Cfg {
+        let n = quads.len();
+        let target_index = |quad: &Quadruple| quad.jump_target().filter(|t| (1..=n).contains(t)).map(|t| t - 1);
+
+        // leaders: the first quadruple, every `func`, every jump target and
+        // every quadruple following a jump or return
+        let mut leader = vec![false; n];
+        for (i, quad) in quads.iter().enumerate() {
+            if i == 0 || quad.op == Opcode::Func {
+                leader[i] = true;
+            }
+            if let Some(target) = target_index(quad) {
+                leader[target] = true;
+            }
+            if (quad.op.is_jump() || quad.op == Opcode::Return) && i + 1 < n {
+                leader[i + 1] = true;
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut block_of = Vec::with_capacity(n);
+        for (i, &is_leader) in leader.iter().enumerate() {
+            if is_leader {
+                blocks.push(BasicBlock {
+                    id: blocks.len(),
+                    start: i,
+                    end: i,
+                    preds: Vec::new(),
+                    succs: Vec::new(),
+                });
+            }
+            let block = blocks.last_mut().unwrap();
+            block.end = i + 1;
+            block_of.push(block.id);
+        }
+
+        for id in 0..blocks.len() {
+            let end = blocks[id].end;
+            let last = &quads[end - 1];
+            let mut succs = Vec::new();
+            let falls_through = !matches!(last.op, Opcode::Jump | Opcode::Return);
+            if falls_through && end < n && quads[end].op != Opcode::Func {
+                succs.push(block_of[end]);
+            }
+            if let Some(target) = target_index(last) && !succs.contains(&block_of[target]) {
+                succs.push(block_of[target]);
+            }
+            for &succ in &succs {
+                blocks[succ].preds.push(id);
+            }
+            blocks[id].succs = succs;
+        }
+
+        let mut functions: Vec<Range<BlockId>> = Vec::new();
+        for block in &blocks {
+            if block.id == 0 || quads[block.start].op == Opcode::Func {
+                functions.push(block.id..block.id + 1);
+            }
+            else {
+                functions.last_mut().unwrap().end = block.id + 1;
+            }
+        }
+
+        Cfg {
+            blocks,
+            block_of,
+            functions,
+        }
+    }
+
+    /// Whether every block can be reached from the entry of its function.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reached = vec![false; self.blocks.len()];
+        let mut stack: Vec<BlockId> = self.functions.iter().map(|f| f.start).collect();
+        while let Some(id) = stack.pop() {
+            if !reached[id] {
+                reached[id] = true;
+                stack.extend(&self.blocks[id].succs);
+            }
+        }
+        reached
+    }
+
+    /// Name of the function whose blocks are `range`, empty for quadruples
+    /// before the first `func`.
+    pub fn function_name(&self, quads: &[Quadruple], range: &Range<BlockId>) -> String {
+        let first = &quads[self.blocks[range.start].start];
+        match (&first.op, &first.arg1) {
+            (Opcode::Func, Operand::Func(name)) => name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Renders the graph in Graphviz DOT, one cluster per function. Edges
+    /// leaving a conditional jump are labelled `T` and `F`.
+    pub fn to_dot(&self, quads: &[Quadruple]) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for (i, range) in self.functions.iter().enumerate() {
+            let name = self.function_name(quads, range);
+            writeln!(dot, "    subgraph cluster_{} {{", i).unwrap();
+            writeln!(dot, "        label=\"{}\";", escape(&name)).unwrap();
+            for block in &self.blocks[range.clone()] {
+                let mut label = format!("B{}\\l", block.id);
+                for index in block.range() {
+                    label.push_str(&escape(&format!("{}: {}", index + 1, quads[index])));
+                    label.push_str("\\l");
+                }
+                writeln!(dot, "        B{} [label=\"{}\"];", block.id, label).unwrap();
+            }
+            dot.push_str("    }\n");
+        }
+        for block in &self.blocks {
+            let last = &quads[block.end - 1];
+            for &succ in &block.succs {
+                let label = match last.op {
+                    Opcode::CondJump(_) if last.jump_target() == Some(self.blocks[succ].start + 1) => " [label=\"T\"]",
+                    Opcode::CondJump(_) => " [label=\"F\"]",
+                    _ => "",
+                };
+                writeln!(dot, "    B{} -> B{}{};", block.id, succ, label).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse_quadruples;
+    use crate::test_util::compile;
+
+    fn edges(cfg: &Cfg) -> Vec<(BlockId, Vec<BlockId>, Vec<BlockId>)> {
+        cfg.blocks.iter().map(|b| (b.id, b.preds.clone(), b.succs.clone())).collect()
+    }
+
+    #[test]
+    fn test_if_else() {
+        let quads = compile("int main() { int x = 1; if (x > 0) { x = 2; } else { x = 3; } return x; }");
+        // 1: (func, main, , )      B0
+        // 2: (=, 1, , x)
+        // 3: (j>, x, 0, 5)
+        // 4: (j, , , 7)            B1
+        // 5: (=, 2, , x)           B2
+        // 6: (j, , , 8)
+        // 7: (=, 3, , x)           B3
+        // 8: (return, x, , )       B4
+        let cfg = Cfg::build(&quads);
+        let ranges: Vec<_> = cfg.blocks.iter().map(|b| b.range()).collect();
+        assert_eq!(ranges, vec![0..3, 3..4, 4..6, 6..7, 7..8]);
+        assert_eq!(edges(&cfg), vec![
+            (0, vec![], vec![1, 2]),
+            (1, vec![0], vec![3]),
+            (2, vec![0], vec![4]),
+            (3, vec![1], vec![4]),
+            (4, vec![2, 3], vec![]),
+        ]);
+        assert_eq!(cfg.block_of, vec![0, 0, 0, 1, 2, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_while_and_functions() {
+        let quads = compile("int f(int n) { while (n > 0) { n = n - 1; } }
+            int main() { return f(3); }");
+        // 1: (func, f, , )         B0
+        // 2: (formal, , , n)
+        // 3: (j>, n, 0, 5)         B1
+        // 4: (j, , , 8)            B2
+        // 5: (-, n, 1, t1)         B3
+        // 6: (=, t1, , n)
+        // 7: (j, , , 3)
+        // 8: (return, 0, , )       B4
+        // 9: (func, main, , )      B5
+        let cfg = Cfg::build(&quads);
+        assert_eq!(edges(&cfg), vec![
+            (0, vec![], vec![1]),
+            (1, vec![0, 3], vec![2, 3]),
+            (2, vec![1], vec![4]),
+            (3, vec![1], vec![1]),
+            (4, vec![2], vec![]),
+            (5, vec![], vec![]),
+        ]);
+        assert_eq!(cfg.functions, vec![0..5, 5..6]);
+        assert_eq!(cfg.function_name(&quads, &cfg.functions[1]), "main");
+    }
+
+    #[test]
+    fn test_leaving_a_function() {
+        let quads = parse_quadruples("
+            (func, f, , )
+            (=, 1, , x)
+            (func, main, , )
+            (j!=, 1, 0, 6)
+            (return, 0, , )
+        ").unwrap();
+        let cfg = Cfg::build(&quads);
+        // neither running into `main` nor jumping past the end is an edge
+        assert_eq!(edges(&cfg), vec![
+            (0, vec![], vec![]),
+            (1, vec![], vec![2]),
+            (2, vec![1], vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let quads = compile("int main() { int x = 1; while (x < 3) { x = x + 1; } return x; }");
+        let dot = Cfg::build(&quads).to_dot(&quads);
+        assert_eq!(dot, "digraph cfg {
+    node [shape=box, fontname=monospace];
+    subgraph cluster_0 {
+        label=\"main\";
+        B0 [label=\"B0\\l1: (func, main, , )\\l2: (=, 1, , x)\\l\"];
+        B1 [label=\"B1\\l3: (j<, x, 3, 5)\\l\"];
+        B2 [label=\"B2\\l4: (j, , , 8)\\l\"];
+        B3 [label=\"B3\\l5: (+, x, 1, t1)\\l6: (=, t1, , x)\\l7: (j, , , 3)\\l\"];
+        B4 [label=\"B4\\l8: (return, x, , )\\l\"];
+    }
+    B0 -> B1;
+    B1 -> B2 [label=\"F\"];
+    B1 -> B3 [label=\"T\"];
+    B2 -> B4;
+    B3 -> B1;
+}
+");
+    }
+}
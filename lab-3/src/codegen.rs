@@ -1,16 +1,25 @@
-use crate::ast::{Block, Expr, Function, IdentType, Stmt};
+use crate::ast::{Block, Expr, Function, IdentType, Program, Stmt};
+use crate::ir::{self, temp_index, BinOp, Opcode, Operand, Quadruple, RelOp};
+use crate::token::Span;
+
+pub type ScopeId = usize;
 
 #[derive(Debug, Clone)]
 pub struct Symbol {
     pub name: String,
+    /// name used for the symbol in the generated quadruples; unique within
+    /// its function so that shadowing declarations stay distinct
+    pub ir_name: String,
     pub symbol_type: SymbolType,
     pub data_type: DataType,
     pub scope_level: usize,
+    pub scope_id: ScopeId,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SymbolType {
     Variable,
+    Function { param_count: usize },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,85 +27,135 @@ pub enum DataType {
     Int,
 }
 
+/// A lexical scope. Scopes are never discarded, so once generation has
+/// finished the whole tree is still there to be printed.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub id: ScopeId,
+    pub parent: Option<ScopeId>,
+    /// nesting depth, 0 for the global scope
+    pub level: usize,
+    /// indices into `SymbolTable::symbols`, in declaration order
+    pub symbols: Vec<usize>,
+    pub children: Vec<ScopeId>,
+}
+
+/// Symbols organised as a tree of scopes. Scope `0` is the global scope;
+/// `enter_scope` opens a new child of the current scope and `exit_scope`
+/// returns to its parent. A name is resolved by walking from the current
+/// scope up through its ancestors, so declarations in sibling blocks never
+/// see each other.
 #[derive(Debug)]
 pub struct SymbolTable {
     pub symbols: Vec<Symbol>,
-    pub scopes: Vec<usize>,
-    pub current_scope: usize,
+    pub scopes: Vec<Scope>,
+    pub current_scope: ScopeId,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SymbolTable {
+    pub const GLOBAL_SCOPE: ScopeId = 0;
+
     pub fn new() -> Self {
         SymbolTable {
             symbols: Vec::new(),
-            scopes: Vec::new(),
-            current_scope: 0,
+            scopes: vec![Scope {
+                id: Self::GLOBAL_SCOPE,
+                parent: None,
+                level: 0,
+                symbols: Vec::new(),
+                children: Vec::new(),
+            }],
+            current_scope: Self::GLOBAL_SCOPE,
         }
     }
 
-    pub fn enter_scope(&mut self) {
-        self.scopes.push(self.symbols.len());
-        self.current_scope += 1;
+    pub fn enter_scope(&mut self) -> ScopeId {
+        let id = self.scopes.len();
+        let parent = self.current_scope;
+        self.scopes.push(Scope {
+            id,
+            parent: Some(parent),
+            level: self.scopes[parent].level + 1,
+            symbols: Vec::new(),
+            children: Vec::new(),
+        });
+        self.scopes[parent].children.push(id);
+        self.current_scope = id;
+        id
     }
 
     pub fn exit_scope(&mut self) {
-        if self.scopes.pop().is_some() {
-            self.current_scope -= 1;
+        if let Some(parent) = self.scopes[self.current_scope].parent {
+            self.current_scope = parent;
         }
     }
 
-    pub fn add_symbol(&mut self, name: String, symbol_type: SymbolType, data_type: DataType) -> Result<(), CompilationError> {
-        for symbol in self.symbols.iter().rev() {
-            if symbol.name == name && symbol.scope_level == self.current_scope {
-                return Err(CompilationError::DuplicateDeclaration {
-                    name,
-                    location: None,
-                });
-            }
-            if symbol.scope_level < self.current_scope {
-                break;
-            }
+    pub fn add_symbol(&mut self, name: String, symbol_type: SymbolType, data_type: DataType, location: Option<Location>) -> Result<&Symbol, CompilationError> {
+        let scope = &self.scopes[self.current_scope];
+        if scope.symbols.iter().any(|&i| self.symbols[i].name == name) {
+            return Err(CompilationError::DuplicateDeclaration {
+                name,
+                location,
+            });
+        }
+        // the first declaration of a name in a function keeps it, later
+        // ones become `x.1`, `x.2`, ...; a variable spelled like a
+        // temporary starts at `t1.0` so that the text form stays unambiguous
+        let owner = self.function_scope(scope.id);
+        let earlier = self.symbols.iter().filter(|s| s.name == name && self.function_scope(s.scope_id) == owner).count();
+        let ir_name = if earlier == 0 && temp_index(&name).is_none() {
+            name.clone()
         }
+        else {
+            format!("{}.{}", name, earlier)
+        };
         let symbol = Symbol {
-            name: name.clone(),
+            name,
+            ir_name,
             symbol_type,
             data_type,
-            scope_level: self.current_scope,
+            scope_level: scope.level,
+            scope_id: scope.id,
         };
+        self.scopes[self.current_scope].symbols.push(self.symbols.len());
         self.symbols.push(symbol);
-        Ok(())
+        Ok(self.symbols.last().unwrap())
     }
 
-    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
-        for symbol in self.symbols.iter().rev() {
-            if symbol.name == name && symbol.scope_level <= self.current_scope {
-                return Some(symbol);
-            }
+    /// The outermost non-global scope containing `id`, i.e. the scope of
+    /// the enclosing function body.
+    fn function_scope(&self, mut id: ScopeId) -> ScopeId {
+        while self.scopes[id].level > 1 {
+            id = self.scopes[id].parent.unwrap();
         }
-        None
+        id
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct Quadruple {
-    pub op: String,
-    pub arg1: String,
-    pub arg2: String,
-    pub result: String,
-}
 
-impl Quadruple {
-    pub fn new(op: &str, arg1: &str, arg2: &str, result: &str) -> Self {
-        Quadruple {
-            op: op.to_string(),
-            arg1: arg1.to_string(),
-            arg2: arg2.to_string(),
-            result: result.to_string(),
+    /// Resolves `name` from the current scope outwards.
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        let mut scope = Some(self.current_scope);
+        while let Some(id) = scope {
+            let found = self.scopes[id]
+                .symbols
+                .iter()
+                .map(|&i| &self.symbols[i])
+                .find(|symbol| symbol.name == name);
+            if found.is_some() {
+                return found;
+            }
+            scope = self.scopes[id].parent;
         }
+        None
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
@@ -108,6 +167,12 @@ impl Location {
     }
 }
 
+impl From<Span> for Location {
+    fn from(span: Span) -> Self {
+        Location::new(span.start.line, span.start.column)
+    }
+}
+
 #[derive(Debug)]
 pub enum CompilationError {
     UndeclaredVariable {
@@ -127,6 +192,23 @@ pub enum CompilationError {
         message: String,
         location: Option<Location>,
     },
+    UndefinedFunction {
+        name: String,
+        location: Option<Location>,
+    },
+    DuplicateFunction {
+        name: String,
+        location: Option<Location>,
+    },
+    ArgumentCountMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+        location: Option<Location>,
+    },
+    DivisionByZero {
+        location: Option<Location>,
+    },
 }
 
 impl std::fmt::Display for CompilationError {
@@ -160,18 +242,101 @@ impl std::fmt::Display for CompilationError {
                 }
                 Ok(())
             }
+            CompilationError::UndefinedFunction { name, location } => {
+                write!(f, "Undefined function '{}'", name)?;
+                if let Some(loc) = location {
+                    write!(f, " at line {}, column {}", loc.line, loc.column)?;
+                }
+                Ok(())
+            }
+            CompilationError::DuplicateFunction { name, location } => {
+                write!(f, "Duplicate definition of function '{}'", name)?;
+                if let Some(loc) = location {
+                    write!(f, " at line {}, column {}", loc.line, loc.column)?;
+                }
+                Ok(())
+            }
+            CompilationError::ArgumentCountMismatch { name, expected, found, location } => {
+                write!(f, "Function '{}' takes {} argument(s) but {} were supplied", name, expected, found)?;
+                if let Some(loc) = location {
+                    write!(f, " at line {}, column {}", loc.line, loc.column)?;
+                }
+                Ok(())
+            }
+            CompilationError::DivisionByZero { location } => {
+                write!(f, "Division by zero")?;
+                if let Some(loc) = location {
+                    write!(f, " at line {}, column {}", loc.line, loc.column)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+fn merge(mut a: Vec<usize>, b: Vec<usize>) -> Vec<usize> {
+    a.extend(b);
+    a
+}
+
+/// The two exits of the jumping code generated for a condition: indices of
+/// the jumps whose targets are still to be backpatched, taken when the
+/// condition holds (`truelist`) or does not hold (`falselist`). Every
+/// condition leaves through exactly one of them, control never falls
+/// through.
+#[derive(Debug, Default)]
+struct CondJumps {
+    truelist: Vec<usize>,
+    falselist: Vec<usize>,
+}
+
+/// Evaluates `expr` if it only involves integer literals, with the
+/// wrapping semantics of i32. Returns `None` for anything that depends on a
+/// variable or a call, or that would divide by zero.
+fn const_value(expr: &Expr) -> Option<i32> {
+    match expr {
+        Expr::Number(n, _) => Some(*n),
+        Expr::UnaryExpr { op, operand, .. } => {
+            let v = const_value(operand)?;
+            match op.as_str() {
+                "-" => Some(v.wrapping_neg()),
+                "!" => Some((v == 0) as i32),
+                _ => Some(v),
+            }
+        }
+        Expr::BinaryExpr { op, lhs, rhs, .. } => {
+            let l = const_value(lhs)?;
+            let r = const_value(rhs)?;
+            match op.as_str() {
+                "&&" => Some((l != 0 && r != 0) as i32),
+                "||" => Some((l != 0 || r != 0) as i32),
+                _ => BinOp::from_symbol(op)?.eval(l, r),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Generates quadruples for a whole program into one list. Each function
+/// starts with `(func, name, , )`, followed by one `(formal, , , p)` per
+/// parameter in declaration order. A call evaluates its arguments, passes
+/// them with `(param, x, , )` and then emits `(call, name, argc, t)`, where
+/// `t` receives the return value.
 #[derive(Debug)]
 pub struct CodeGenerator {
+    /// functions in the global scope, with a child scope for each body
     pub symbol_table: SymbolTable,
     pub quadruples: Vec<Quadruple>,
     pub temp_counter: usize,
     pub errors: Vec<CompilationError>,
 }
 
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeGenerator {
     pub fn new() -> Self {
         CodeGenerator {
@@ -182,17 +347,27 @@ impl CodeGenerator {
         }
     }
 
-    pub fn generate(&mut self, ast: &Function) -> Result<(), Vec<CompilationError>> {
+    pub fn generate(&mut self, program: &Program) -> Result<(), Vec<CompilationError>> {
         self.symbol_table = SymbolTable::new();
         self.quadruples.clear();
         self.temp_counter = 0;
         self.errors.clear();
 
-        self.symbol_table.enter_scope();
-
-        self.process_block(&ast.block)?;
+        // declare every function first so that functions can be called
+        // before their definition
+        for func in &program.functions {
+            let symbol_type = SymbolType::Function { param_count: func.params.len() };
+            if self.symbol_table.add_symbol(func.name.clone(), symbol_type, DataType::Int, None).is_err() {
+                self.errors.push(CompilationError::DuplicateFunction {
+                    name: func.name.clone(),
+                    location: Some(func.span.into()),
+                });
+            }
+        }
 
-        self.symbol_table.exit_scope();
+        for func in &program.functions {
+            self.process_function(func)?;
+        }
 
         if !self.errors.is_empty() {
             return Err(std::mem::take(&mut self.errors));
@@ -200,6 +375,63 @@ impl CodeGenerator {
         Ok(())
     }
 
+    fn process_function(&mut self, func: &Function) -> Result<(), Vec<CompilationError>> {
+        self.emit(Opcode::Func, Operand::Func(func.name.clone()), Operand::Empty, Operand::Empty);
+
+        // parameters share the scope of the function body
+        self.symbol_table.enter_scope();
+
+        for param in &func.params {
+            let data_type = match param.ident_type {
+                IdentType::Int => DataType::Int,
+            };
+            match self.symbol_table.add_symbol(param.name.clone(), SymbolType::Variable, data_type, Some(param.span.into())) {
+                Ok(symbol) => {
+                    let ir_name = symbol.ir_name.clone();
+                    self.emit(Opcode::Formal, Operand::Empty, Operand::Empty, Operand::Var(ir_name));
+                }
+                Err(e) => {
+                    self.errors.push(e);
+                    self.emit(Opcode::Formal, Operand::Empty, Operand::Empty, Operand::Var(param.name.clone()));
+                }
+            }
+        }
+
+        for stmt in &func.block.stmts {
+            self.process_stmt(stmt)?;
+        }
+
+        // falling off the end of a function returns 0
+        if !matches!(func.block.stmts.last(), Some(Stmt::ReturnStmt(_))) {
+            self.emit(Opcode::Return, Operand::Const(0), Operand::Empty, Operand::Empty);
+        }
+
+        self.symbol_table.exit_scope();
+        Ok(())
+    }
+
+    /// Resolves `name` to the IR name of a variable, reporting an error at
+    /// `span` if it is not one.
+    fn resolve_variable(&mut self, name: &str, span: Span) -> Option<Operand> {
+        match self.symbol_table.lookup(name) {
+            Some(symbol) if symbol.symbol_type == SymbolType::Variable => Some(Operand::Var(symbol.ir_name.clone())),
+            Some(_) => {
+                self.errors.push(CompilationError::GenericSemanticError {
+                    message: format!("'{}' is a function, not a variable", name),
+                    location: Some(span.into()),
+                });
+                None
+            }
+            None => {
+                self.errors.push(CompilationError::UndeclaredVariable {
+                    name: name.to_string(),
+                    location: Some(span.into()),
+                });
+                None
+            }
+        }
+    }
+
     fn process_block(&mut self, block: &Block) -> Result<(), Vec<CompilationError>> {
         self.symbol_table.enter_scope();
 
@@ -216,196 +448,290 @@ impl CodeGenerator {
             Stmt::ReturnStmt(expr) => self.process_return_stmt(expr),
             Stmt::IfStmt { cond, if_block, else_stmt } => self.process_if_stmt(cond, if_block, else_stmt),
             Stmt::WhileStmt { cond, block } => self.process_while_stmt(cond, block),
-            Stmt::AssignmentStmt { lval, rval } => self.process_assignment_stmt(lval, rval),
-            Stmt::DeclareStmt { ident_type, ident, rval } => self.process_declare_stmt(ident_type, ident, rval),
+            Stmt::AssignmentStmt { lval, rval, span } => self.process_assignment_stmt(lval, rval, *span),
+            Stmt::DeclareStmt { ident_type, ident, rval, span } => self.process_declare_stmt(ident_type, ident, rval, *span),
+            // already reported by the parser
+            Stmt::Error(_) => Ok(()),
         }
     }
 
     fn process_return_stmt(&mut self, expr: &Expr) -> Result<(), Vec<CompilationError>> {
         let result = self.process_expr(expr)?;
-        self.emit("return", &result, "", "");
+        self.emit(Opcode::Return, result, Operand::Empty, Operand::Empty);
         Ok(())
     }
 
     fn process_if_stmt(&mut self, cond: &Expr, if_block: &Block, else_stmt: &Option<Block>) -> Result<(), Vec<CompilationError>> {
-        // 处理条件表达式，生成条件跳转
-        let (cond_op, lhs, rhs) = self.extract_condition(cond)?;
-        
-        // 生成条件跳转指令（占位符目标）
-        let cond_jump_op = format!("j{}", cond_op);
-        let cond_jump_index = self.quadruples.len();
-        self.quadruples.push(Quadruple::new(&cond_jump_op, &lhs, &rhs, "0"));
-        
-        // 生成无条件跳转到 else 部分（占位符）
-        let else_jump_index = self.quadruples.len();
-        self.quadruples.push(Quadruple::new("j", "", "", "0"));
-        
-        // 处理 then 部分
-        let then_start_index = self.quadruples.len() + 1;
+        // 处理条件表达式，得到真出口链和假出口链
+        let jumps = self.process_cond(cond)?;
+
+        // 真出口回填为 then 部分开始
+        self.backpatch(&jumps.truelist, self.next_quad());
         self.process_block(if_block)?;
-        
-        // 如果有 else 部分，生成跳转到 if 语句结束
-        let after_else_jump_index = if else_stmt.is_some() {
-            let jump_index = self.quadruples.len();
-            self.quadruples.push(Quadruple::new("j", "", "", "0"));
-            Some(jump_index)
-        } else {
-            None
-        };
-        
-        // else 部分开始位置
-        let else_start_index = self.quadruples.len() + 1;
-        
-        // 处理 else 部分（如果有）
-        if let Some(else_blk) = else_stmt {
-            self.process_block(else_blk)?;
-        }
-        
-        // if 语句结束位置
-        let after_if_index = self.quadruples.len() + 1;
-        
-        // 回填跳转目标
-        // 条件跳转目标：then 部分开始
-        self.quadruples[cond_jump_index].result = then_start_index.to_string();
-        
-        // 无条件跳转到 else 的目标：else 部分开始（如果没有 else 部分，跳转到 if 语句结束）
-        let else_jump_target = if else_stmt.is_some() { else_start_index } else { after_if_index };
-        self.quadruples[else_jump_index].result = else_jump_target.to_string();
-        
-        // 回填跳转到 if 语句结束的跳转（如果有）
-        if let Some(jump_index) = after_else_jump_index {
-            self.quadruples[jump_index].result = after_if_index.to_string();
+
+        match else_stmt {
+            Some(else_blk) => {
+                // then 部分结束后跳过 else 部分（目标待回填）
+                let exit_jump_index = self.quadruples.len();
+                self.emit(Opcode::Jump, Operand::Empty, Operand::Empty, Operand::Empty);
+
+                // 假出口回填为 else 部分开始
+                self.backpatch(&jumps.falselist, self.next_quad());
+                self.process_block(else_blk)?;
+
+                self.backpatch(&[exit_jump_index], self.next_quad());
+            }
+            None => {
+                // 假出口回填为 if 语句结束
+                self.backpatch(&jumps.falselist, self.next_quad());
+            }
         }
-        
+
         Ok(())
     }
 
     fn process_while_stmt(&mut self, cond: &Expr, block: &Block) -> Result<(), Vec<CompilationError>> {
-        // 循环开始位置
-        let loop_start_index = self.quadruples.len() + 1;
-        
-        // 处理条件表达式
-        let (cond_op, lhs, rhs) = self.extract_condition(cond)?;
-        
-        // 生成条件跳转到循环结束的指令（占位符）
-        let cond_jump_op = format!("j{}", cond_op);
-        let cond_jump_index = self.quadruples.len();
-        self.quadruples.push(Quadruple::new(&cond_jump_op, &lhs, &rhs, "0"));
-        
-        // 处理循环体
+        // 循环开始位置，每次迭代都重新计算条件
+        let loop_start = self.next_quad();
+
+        let jumps = self.process_cond(cond)?;
+
+        // 条件为真进入循环体
+        self.backpatch(&jumps.truelist, self.next_quad());
         self.process_block(block)?;
-        
-        // 生成跳回循环开始的指令
-        let back_jump_index = self.quadruples.len();
-        self.quadruples.push(Quadruple::new("j", "", "", "0"));
-        
-        // 循环结束位置
-        let loop_end_index = self.quadruples.len() + 1;
-        
-        // 回填跳转目标
-        // 条件跳转：不满足条件时跳转到循环结束
-        self.quadruples[cond_jump_index].result = loop_end_index.to_string();
-        
+
         // 跳回循环开始
-        self.quadruples[back_jump_index].result = loop_start_index.to_string();
-        
+        self.emit(Opcode::Jump, Operand::Empty, Operand::Empty, Operand::Label(loop_start));
+
+        // 条件为假离开循环
+        self.backpatch(&jumps.falselist, self.next_quad());
+
         Ok(())
     }
 
-    fn extract_condition(&mut self, expr: &Expr) -> Result<(String, String, String), Vec<CompilationError>> {
+    /// Generates jumping code for a condition. `&&`, `||` and `!` are
+    /// short-circuited without materialising any value, a relational
+    /// expression becomes a single `j<op>`, and any other expression is true
+    /// when its value is non-zero.
+    fn process_cond(&mut self, expr: &Expr) -> Result<CondJumps, Vec<CompilationError>> {
         match expr {
-            Expr::BinaryExpr { op, lhs, rhs } => {
+            Expr::BinaryExpr { op, lhs, rhs, .. } if op == "&&" => {
+                let lhs = self.process_cond(lhs)?;
+                // 左侧为真时继续计算右侧
+                self.backpatch(&lhs.truelist, self.next_quad());
+                let rhs = self.process_cond(rhs)?;
+                Ok(CondJumps {
+                    truelist: rhs.truelist,
+                    falselist: merge(lhs.falselist, rhs.falselist),
+                })
+            }
+            Expr::BinaryExpr { op, lhs, rhs, .. } if op == "||" => {
+                let lhs = self.process_cond(lhs)?;
+                // 左侧为假时继续计算右侧
+                self.backpatch(&lhs.falselist, self.next_quad());
+                let rhs = self.process_cond(rhs)?;
+                Ok(CondJumps {
+                    truelist: merge(lhs.truelist, rhs.truelist),
+                    falselist: rhs.falselist,
+                })
+            }
+            Expr::UnaryExpr { op, operand, .. } if op == "!" => {
+                let jumps = self.process_cond(operand)?;
+                Ok(CondJumps {
+                    truelist: jumps.falselist,
+                    falselist: jumps.truelist,
+                })
+            }
+            Expr::BinaryExpr { op, lhs, rhs, .. } if RelOp::from_symbol(op).is_some() => {
+                let op = RelOp::from_symbol(op).unwrap();
                 let left = self.process_expr(lhs)?;
                 let right = self.process_expr(rhs)?;
-                Ok((op.to_string(), left, right))
+                Ok(self.emit_cond_jumps(op, left, right))
             }
             _ => {
-                // 对于非二元表达式，假设表达式值为条件（0为假，非0为真）
-                // 生成临时变量存储表达式值
-                let cond_value = self.process_expr(expr)?;
-                Ok(("z".to_string(), cond_value, "0".to_string()))
+                // 非关系表达式：值非 0 为真
+                let value = self.process_expr(expr)?;
+                Ok(self.emit_cond_jumps(RelOp::Ne, value, Operand::Const(0)))
             }
         }
     }
 
-    fn process_assignment_stmt(&mut self, lval: &str, rval: &Expr) -> Result<(), Vec<CompilationError>> {
-        if self.symbol_table.lookup(lval).is_none() {
-            self.errors.push(CompilationError::UndeclaredVariable {
-                name: lval.to_string(),
-                location: None,
-            });
-            return Ok(());
+    /// Emits `(j<op>, lhs, rhs, _)` taken when the condition holds, followed
+    /// by `(j, , , _)` taken otherwise, both with targets still to be
+    /// backpatched.
+    fn emit_cond_jumps(&mut self, op: RelOp, lhs: Operand, rhs: Operand) -> CondJumps {
+        let true_index = self.quadruples.len();
+        self.emit(Opcode::CondJump(op), lhs, rhs, Operand::Empty);
+        let false_index = self.quadruples.len();
+        self.emit(Opcode::Jump, Operand::Empty, Operand::Empty, Operand::Empty);
+        CondJumps {
+            truelist: vec![true_index],
+            falselist: vec![false_index],
         }
+    }
+
+    /// Fills `target` into the result of every jump in `list`.
+    fn backpatch(&mut self, list: &[usize], target: usize) {
+        for &index in list {
+            self.quadruples[index].result = Operand::Label(target);
+        }
+    }
+
+    /// Number of the next quadruple to be emitted, as used in jump targets.
+    fn next_quad(&self) -> usize {
+        self.quadruples.len() + 1
+    }
+
+    fn process_assignment_stmt(&mut self, lval: &str, rval: &Expr, span: Span) -> Result<(), Vec<CompilationError>> {
+        let Some(lval) = self.resolve_variable(lval, span) else {
+            return Ok(());
+        };
 
         let result = self.process_expr(rval)?;
-        self.emit("=", &result, "", lval);
+        self.emit(Opcode::Assign, result, Operand::Empty, lval);
         Ok(())
     }
 
-    fn process_declare_stmt(&mut self, ident_type: &IdentType, ident: &str, rval: &Option<Expr>) -> Result<(), Vec<CompilationError>> {
+    fn process_declare_stmt(&mut self, ident_type: &IdentType, ident: &str, rval: &Option<Expr>, span: Span) -> Result<(), Vec<CompilationError>> {
         let data_type = match ident_type {
             IdentType::Int => DataType::Int,
         };
-        if let Err(e) = self.symbol_table.add_symbol(ident.to_string(), SymbolType::Variable, data_type) {
-            self.errors.push(e);
-            return Ok(());
-        }
+        // the initialiser cannot see the variable being declared
+        let result = match rval {
+            Some(expr) => Some(self.process_expr(expr)?),
+            None => None,
+        };
+        let ident = match self.symbol_table.add_symbol(ident.to_string(), SymbolType::Variable, data_type, Some(span.into())) {
+            Ok(symbol) => Operand::Var(symbol.ir_name.clone()),
+            Err(e) => {
+                self.errors.push(e);
+                return Ok(());
+            }
+        };
 
-        if let Some(expr) = rval {
-            let result = self.process_expr(expr)?;
-            self.emit("=", &result, "", ident);
+        if let Some(result) = result {
+            self.emit(Opcode::Assign, result, Operand::Empty, ident);
         }
         Ok(())
     }
 
-    fn process_expr(&mut self, expr: &Expr) -> Result<String, Vec<CompilationError>> {
+    fn process_expr(&mut self, expr: &Expr) -> Result<Operand, Vec<CompilationError>> {
         match expr {
-            Expr::Number(n) => Ok(n.to_string()),
-            Expr::Var(name) => {
-                if self.symbol_table.lookup(name).is_none() {
-                    self.errors.push(CompilationError::UndeclaredVariable {
-                        name: name.clone(),
-                        location: None,
+            Expr::Number(n, _) => Ok(Operand::Const(*n)),
+            Expr::Var(name, span) => {
+                Ok(self.resolve_variable(name, *span).unwrap_or(Operand::Const(0)))
+            }
+            Expr::BinaryExpr { op, .. } if op == "&&" || op == "||" => {
+                // 通过跳转计算逻辑值: 真出口赋 1，假出口赋 0
+                let jumps = self.process_cond(expr)?;
+                let temp = self.new_temp();
+                self.backpatch(&jumps.truelist, self.next_quad());
+                self.emit(Opcode::Assign, Operand::Const(1), Operand::Empty, temp.clone());
+                let after = self.next_quad() + 2;
+                self.emit(Opcode::Jump, Operand::Empty, Operand::Empty, Operand::Label(after));
+                self.backpatch(&jumps.falselist, self.next_quad());
+                self.emit(Opcode::Assign, Operand::Const(0), Operand::Empty, temp.clone());
+                Ok(temp)
+            }
+            Expr::BinaryExpr { op, lhs, rhs, .. } => {
+                if (op == "/" || op == "%") && const_value(rhs) == Some(0) {
+                    self.errors.push(CompilationError::DivisionByZero {
+                        location: Some(rhs.span().into()),
                     });
-                    Ok("0".to_string())
-                } else {
-                    Ok(name.clone())
                 }
-            }
-            Expr::BinaryExpr { op, lhs, rhs } => {
+                let op = BinOp::from_symbol(op).expect("unknown binary operator");
                 let left = self.process_expr(lhs)?;
                 let right = self.process_expr(rhs)?;
                 let temp = self.new_temp();
-                self.emit(&op.to_string(), &left, &right, &temp);
+                self.emit(Opcode::Binary(op), left, right, temp.clone());
+                Ok(temp)
+            }
+            Expr::UnaryExpr { op, operand, .. } => {
+                let value = self.process_expr(operand)?;
+                let op = match op.as_str() {
+                    "+" => return Ok(value),
+                    "-" => Opcode::Neg,
+                    _ => Opcode::Not,
+                };
+                let temp = self.new_temp();
+                self.emit(op, value, Operand::Empty, temp.clone());
+                Ok(temp)
+            }
+            Expr::Call { name, args, span } => {
+                match self.symbol_table.lookup(name).map(|s| &s.symbol_type) {
+                    None => {
+                        self.errors.push(CompilationError::UndefinedFunction {
+                            name: name.clone(),
+                            location: Some((*span).into()),
+                        });
+                    }
+                    Some(SymbolType::Variable) => {
+                        self.errors.push(CompilationError::GenericSemanticError {
+                            message: format!("'{}' is a variable, not a function", name),
+                            location: Some((*span).into()),
+                        });
+                    }
+                    Some(&SymbolType::Function { param_count }) if param_count != args.len() => {
+                        self.errors.push(CompilationError::ArgumentCountMismatch {
+                            name: name.clone(),
+                            expected: param_count,
+                            found: args.len(),
+                            location: Some((*span).into()),
+                        });
+                    }
+                    Some(_) => {}
+                }
+
+                // evaluate every argument before passing any of them, so
+                // that nested calls do not interleave their params
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.process_expr(arg)?);
+                }
+                for value in values {
+                    self.emit(Opcode::Param, value, Operand::Empty, Operand::Empty);
+                }
+                let temp = self.new_temp();
+                self.emit(Opcode::Call, Operand::Func(name.clone()), Operand::Const(args.len() as i32), temp.clone());
                 Ok(temp)
             }
+            // already reported by the parser
+            Expr::Error(_) => Ok(Operand::Const(0)),
         }
     }
 
-    fn emit(&mut self, op: &str, arg1: &str, arg2: &str, result: &str) {
+    fn emit(&mut self, op: Opcode, arg1: Operand, arg2: Operand, result: Operand) {
         self.quadruples.push(Quadruple::new(op, arg1, arg2, result));
     }
 
 
 
-    fn new_temp(&mut self) -> String {
+    fn new_temp(&mut self) -> Operand {
         self.temp_counter += 1;
-        format!("t{}", self.temp_counter)
+        Operand::Temp(self.temp_counter)
     }
 
 
 
     pub fn print_quadruples(&self) {
-        for (i, quad) in self.quadruples.iter().enumerate() {
-            println!("{}: ({}, {}, {}, {})", i + 1, quad.op, quad.arg1, quad.arg2, quad.result);
-        }
+        print!("{}", ir::format_quadruples(&self.quadruples));
     }
 
     pub fn print_symbol_table(&self) {
         println!("Symbol Table:");
-        for symbol in &self.symbol_table.symbols {
-            println!("  {}: {:?} {:?} scope {}", 
-                symbol.name, symbol.symbol_type, symbol.data_type, symbol.scope_level);
+        self.print_scope(SymbolTable::GLOBAL_SCOPE, 1);
+    }
+
+    fn print_scope(&self, id: ScopeId, depth: usize) {
+        let indent = "  ".repeat(depth);
+        let scope = &self.symbol_table.scopes[id];
+        println!("{}scope {}:", indent, scope.id);
+        for &i in &scope.symbols {
+            let symbol = &self.symbol_table.symbols[i];
+            println!("{}  {} -> {}: {:?} {:?}", indent, symbol.name, symbol.ir_name, symbol.symbol_type, symbol.data_type);
+        }
+        for &child in &scope.children {
+            self.print_scope(child, depth + 1);
         }
     }
 }
@@ -413,32 +739,39 @@ impl CodeGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::{Function, FunctionType, Block, Stmt, Expr, IdentType};
+    use crate::ast::{Function, FunctionType, Block, Stmt, Expr, IdentType, Program};
+    use crate::test_util::parse;
+    use crate::token::Span;
 
-    fn create_test_ast() -> Function {
+    fn create_test_ast() -> Program {
         let block = Block {
             stmts: vec![
                 Stmt::DeclareStmt {
                     ident_type: IdentType::Int,
                     ident: "x".to_string(),
-                    rval: Some(Expr::Number(5)),
+                    rval: Some(Expr::Number(5, Span::default())),
+                    span: Span::default(),
                 },
                 Stmt::AssignmentStmt {
                     lval: "x".to_string(),
                     rval: Expr::BinaryExpr {
                          op: "+".to_string(),
-                        lhs: Box::new(Expr::Var("x".to_string())),
-                        rhs: Box::new(Expr::Number(1)),
+                        lhs: Box::new(Expr::Var("x".to_string(), Span::default())),
+                        rhs: Box::new(Expr::Number(1, Span::default())),
+                        span: Span::default(),
                     },
+                    span: Span::default(),
                 },
-                Stmt::ReturnStmt(Expr::Var("x".to_string())),
+                Stmt::ReturnStmt(Expr::Var("x".to_string(), Span::default())),
             ],
         };
-        Function {
+        Program::new(vec![Function {
             return_type: FunctionType::Int,
             name: "main".to_string(),
+            params: Vec::new(),
             block,
-        }
+            span: Span::default(),
+        }])
     }
 
     #[test]
@@ -458,15 +791,18 @@ mod tests {
             stmts: vec![
                 Stmt::AssignmentStmt {
                     lval: "y".to_string(),
-                    rval: Expr::Number(3),
+                    rval: Expr::Number(3, Span::default()),
+                    span: Span::default(),
                 },
             ],
         };
-        let ast = Function {
+        let ast = Program::new(vec![Function {
             return_type: FunctionType::Int,
             name: "main".to_string(),
+            params: Vec::new(),
             block,
-        };
+            span: Span::default(),
+        }]);
         let mut codegen = CodeGenerator::new();
         let result = codegen.generate(&ast);
         assert!(result.is_err());
@@ -488,19 +824,23 @@ mod tests {
                     ident_type: IdentType::Int,
                     ident: "x".to_string(),
                     rval: None,
+                    span: Span::default(),
                 },
                 Stmt::DeclareStmt {
                     ident_type: IdentType::Int,
                     ident: "x".to_string(),
                     rval: None,
+                    span: Span::default(),
                 },
             ],
         };
-        let ast = Function {
+        let ast = Program::new(vec![Function {
             return_type: FunctionType::Int,
             name: "main".to_string(),
+            params: Vec::new(),
             block,
-        };
+            span: Span::default(),
+        }]);
         let mut codegen = CodeGenerator::new();
         let result = codegen.generate(&ast);
         assert!(result.is_err());
@@ -512,4 +852,316 @@ mod tests {
             _ => panic!("Expected DuplicateDeclaration error"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_error_location() {
+        let ast = parse("int main() {\n    int x;\n    x = y + 1;\n}");
+        let mut codegen = CodeGenerator::new();
+        let errors = codegen.generate(&ast).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Undeclared variable 'y' at line 3, column 9");
+    }
+
+    fn generate(input: &str) -> Result<CodeGenerator, Vec<CompilationError>> {
+        let mut codegen = CodeGenerator::new();
+        codegen.generate(&parse(input)).map(|_| codegen)
+    }
+
+    fn quads(codegen: &CodeGenerator) -> Vec<String> {
+        codegen.quadruples
+            .iter()
+            .map(|q| q.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_function_call() {
+        let codegen = generate("int add(int a, int b) { return a + b; }
+            int main() { return add(1, add(2, 3)); }").unwrap();
+        assert_eq!(quads(&codegen), vec![
+            "(func, add, , )",
+            "(formal, , , a)",
+            "(formal, , , b)",
+            "(+, a, b, t1)",
+            "(return, t1, , )",
+            "(func, main, , )",
+            "(param, 2, , )",
+            "(param, 3, , )",
+            "(call, add, 2, t2)",
+            "(param, 1, , )",
+            "(param, t2, , )",
+            "(call, add, 2, t3)",
+            "(return, t3, , )",
+        ]);
+    }
+
+    #[test]
+    fn test_function_scopes_are_separate() {
+        let codegen = generate("int f(int x) { int y = x; return y; }
+            int main() { int x = 1; int y = f(x); }").unwrap();
+        let table = &codegen.symbol_table;
+        let scope_symbols = |id: ScopeId| -> Vec<&str> {
+            table.scopes[id].symbols.iter().map(|&i| table.symbols[i].name.as_str()).collect()
+        };
+        assert_eq!(scope_symbols(SymbolTable::GLOBAL_SCOPE), vec!["f", "main"]);
+        assert_eq!(table.scopes[SymbolTable::GLOBAL_SCOPE].children, vec![1, 2]);
+        assert_eq!(scope_symbols(1), vec!["x", "y"]);
+        assert_eq!(scope_symbols(2), vec!["x", "y"]);
+        assert_eq!(quads(&codegen).last().unwrap(), "(return, 0, , )");
+    }
+
+    #[test]
+    fn test_sibling_scopes() {
+        let errors = generate("int main() {
+            int x = 1;
+            if (x) { int y = 1; } else { int y = 2; }
+            while (x) { int y = 3; x = 0; }
+            if (x) { y = 4; }
+            return x;
+        }").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec!["Undeclared variable 'y' at line 5, column 22"]);
+    }
+
+    #[test]
+    fn test_scope_tree() {
+        let mut table = SymbolTable::new();
+        let f = table.enter_scope();
+        table.add_symbol("a".to_string(), SymbolType::Variable, DataType::Int, None).unwrap();
+        let inner = table.enter_scope();
+        table.add_symbol("a".to_string(), SymbolType::Variable, DataType::Int, None).unwrap();
+        assert_eq!(table.lookup("a").unwrap().scope_id, inner);
+        table.exit_scope();
+        assert_eq!(table.lookup("a").unwrap().scope_id, f);
+        table.exit_scope();
+        assert!(table.lookup("a").is_none());
+
+        assert_eq!(table.scopes[inner].parent, Some(f));
+        assert_eq!(table.scopes[inner].level, 2);
+        assert_eq!(table.symbols.len(), 2);
+    }
+
+    #[test]
+    fn test_shadowed_ir_names() {
+        let codegen = generate("int f(int x) {
+            int y = x;
+            if (y) { int x = 2; y = y + x; }
+            while (y < 10) { int x = 3; y = y + x; if (x) { int y = x; } }
+            return x + y;
+        }
+        int main() { int x = 1; return f(x); }").unwrap();
+        let ir_names: Vec<_> = codegen.symbol_table.symbols.iter().map(|s| s.ir_name.as_str()).collect();
+        assert_eq!(ir_names, vec!["f", "main", "x", "y", "x.1", "x.2", "y.1", "x"]);
+        assert_eq!(quads(&codegen)[5..7], ["(=, 2, , x.1)", "(+, y, x.1, t1)"]);
+        assert_eq!(run(&codegen), 13);
+    }
+
+    #[test]
+    fn test_initializer_sees_outer_variable() {
+        let input = "int main() { int x = 1; if (x) { int x = x + 1; return x; } return 0; }";
+        let codegen = generate(input).unwrap();
+        assert_eq!(quads(&codegen)[4..6], ["(+, x, 1, t1)", "(=, t1, , x.1)"]);
+        assert_eq!(crate::evaluator::Evaluator::new(&parse(input)).run(), Ok(run(&codegen)));
+        assert_eq!(run(&codegen), 2);
+
+        let errors = generate("int main() { int y = y; return y; }").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec!["Undeclared variable 'y' at line 1, column 22"]);
+    }
+
+    #[test]
+    fn test_function_and_variable_names() {
+        let errors = generate("int f() { return 1; }
+            int main() { int g = f; return g(); }").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Semantic error: 'f' is a function, not a variable at line 2, column 34",
+            "Semantic error: 'g' is a variable, not a function at line 2, column 44",
+        ]);
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let errors = generate("int f(int x) { return x; }
+            int f() { return 0; }
+            int main() { return f() + g(1); }").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Duplicate definition of function 'f' at line 2, column 17",
+            "Function 'f' takes 1 argument(s) but 0 were supplied at line 3, column 33",
+            "Undefined function 'g' at line 3, column 39",
+        ]);
+    }
+
+    #[test]
+    fn test_duplicate_parameter() {
+        let errors = generate("int f(int x, int x) { return x; }").unwrap_err();
+        assert!(matches!(&errors[0], CompilationError::DuplicateDeclaration { name, .. } if name == "x"));
+    }
+
+    #[test]
+    fn test_unary_expr() {
+        let codegen = generate("int main() { int x = 2; return -x + +x * !x - -3; }").unwrap();
+        assert_eq!(quads(&codegen), vec![
+            "(func, main, , )",
+            "(=, 2, , x)",
+            "(uminus, x, , t1)",
+            "(not, x, , t2)",
+            "(*, x, t2, t3)",
+            "(+, t1, t3, t4)",
+            "(-, t4, -3, t5)",
+            "(return, t5, , )",
+        ]);
+    }
+
+    #[test]
+    fn test_division_and_modulo() {
+        let codegen = generate("int main() { int x = 7; return x / 2 % x; }").unwrap();
+        assert_eq!(quads(&codegen), vec![
+            "(func, main, , )",
+            "(=, 7, , x)",
+            "(/, x, 2, t1)",
+            "(%, t1, x, t2)",
+            "(return, t2, , )",
+        ]);
+    }
+
+    #[test]
+    fn test_constant_division_by_zero() {
+        let errors = generate("int main() {
+            int x = 7;
+            x = x / 0;
+            x = x % (2 - 2 * 1);
+            x = x / (1 - x);
+            return 0 / x;
+        }").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Division by zero at line 3, column 21",
+            "Division by zero at line 4, column 22",
+        ]);
+    }
+
+    #[test]
+    fn test_short_circuit_cond() {
+        let codegen = generate("int main() {
+            int a = 1;
+            int b = 2;
+            if (a > 0 && b < 3 || !(a == b)) { a = 0; } else { b = 0; }
+            return a;
+        }").unwrap();
+        assert_eq!(quads(&codegen), vec![
+            "(func, main, , )",
+            "(=, 1, , a)",
+            "(=, 2, , b)",
+            "(j>, a, 0, 6)",
+            "(j, , , 8)",
+            "(j<, b, 3, 10)",
+            "(j, , , 8)",
+            "(j==, a, b, 12)",
+            "(j, , , 10)",
+            "(=, 0, , a)",
+            "(j, , , 13)",
+            "(=, 0, , b)",
+            "(return, a, , )",
+        ]);
+    }
+
+    #[test]
+    fn test_short_circuit_value() {
+        let codegen = generate("int main() { int a = 1; int b = a > 0 || a < -5; return b; }").unwrap();
+        assert_eq!(quads(&codegen), vec![
+            "(func, main, , )",
+            "(=, 1, , a)",
+            "(j>, a, 0, 7)",
+            "(j, , , 5)",
+            "(j<, a, -5, 7)",
+            "(j, , , 9)",
+            "(=, 1, , t1)",
+            "(j, , , 10)",
+            "(=, 0, , t1)",
+            "(=, t1, , b)",
+            "(return, b, , )",
+        ]);
+    }
+
+    #[test]
+    fn test_while_cond() {
+        let codegen = generate("int main() { int a = 10; while (a > 0 && a != 5) { a = a - 1; } return a; }").unwrap();
+        assert_eq!(quads(&codegen), vec![
+            "(func, main, , )",
+            "(=, 10, , a)",
+            "(j>, a, 0, 5)",
+            "(j, , , 10)",
+            "(j!=, a, 5, 7)",
+            "(j, , , 10)",
+            "(-, a, 1, t1)",
+            "(=, t1, , a)",
+            "(j, , , 3)",
+            "(return, a, , )",
+        ]);
+    }
+
+    fn run(codegen: &CodeGenerator) -> i32 {
+        crate::interpreter::Interpreter::new(&codegen.quadruples).run().unwrap()
+    }
+
+    #[test]
+    fn test_run_while_loop() {
+        let codegen = generate("int main() {
+            int a = 10;
+            int n = 0;
+            while (a > 0) { a = a - 1; n = n + 2; }
+            return n;
+        }").unwrap();
+        assert_eq!(run(&codegen), 20);
+    }
+
+    #[test]
+    fn test_run_while_never_entered() {
+        let codegen = generate("int main() { int a = 0; while (a) { a = 5; } return a + 1; }").unwrap();
+        assert_eq!(run(&codegen), 1);
+    }
+
+    #[test]
+    fn test_run_non_relational_if() {
+        let program = "int main() {
+            int x = X;
+            int r = 0;
+            if (x) { r = 1; } else { r = 2; }
+            if (!x) { r = r * 10; }
+            if (x - 3) { r = r + 100; }
+            return r;
+        }";
+        let result = |x: &str| run(&generate(&program.replace("X", x)).unwrap());
+        assert_eq!(result("0"), 120);
+        assert_eq!(result("3"), 1);
+        assert_eq!(result("5"), 101);
+    }
+
+    #[test]
+    fn test_run_nested_control_flow() {
+        let codegen = generate("int main() {
+            int i = 0;
+            int evens = 0;
+            int odds = 0;
+            while (i < 10 && !(i == 7)) {
+                if (i % 2 == 0 || i == 5) { evens = evens + 1; }
+                else { odds = odds + 1; }
+                i = i + 1;
+            }
+            return evens * 100 + odds * 10 + i;
+        }").unwrap();
+        // i = 0..6: evens 0 2 4 6 and 5, odds 1 3
+        assert_eq!(run(&codegen), 527);
+    }
+
+    #[test]
+    fn test_run_logical_values() {
+        let codegen = generate("int main() {
+            int a = 0;
+            int b = 3;
+            return (a || b) * 1000 + (a && b) * 100 + (b && 2 > 1) * 10 + !a;
+        }").unwrap();
+        assert_eq!(run(&codegen), 1011);
+    }
+}
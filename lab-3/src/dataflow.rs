@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::{Display, Write};
+
+use crate::cfg::{BasicBlock, Cfg};
+use crate::ir::{Operand, Quadruple};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// A data-flow problem over the blocks of a `Cfg`.
+///
+/// Facts form a lattice: `initial` is its top, the value every block starts
+/// from, and `meet` combines the facts flowing in from several blocks.
+/// `boundary` is what flows into a function entry (forward problems) or out
+/// of a block without successors (backward problems).
+pub trait Analysis {
+    type Fact: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+    fn boundary(&self) -> Self::Fact;
+    fn initial(&self) -> Self::Fact;
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact);
+    /// The fact at the other end of `block`, given the one flowing into it:
+    /// OUT from IN for forward problems, IN from OUT for backward ones.
+    fn transfer(&self, block: &BasicBlock, quads: &[Quadruple], fact: &Self::Fact) -> Self::Fact;
+}
+
+/// The facts holding at the entry and exit of every block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution<F> {
+    pub ins: Vec<F>,
+    pub outs: Vec<F>,
+}
+
+impl<F> Solution<F> {
+    /// One line `B<n>: IN = ..., OUT = ...` per block.
+    pub fn format_with(&self, format_fact: impl Fn(&F) -> String) -> String {
+        let mut text = String::new();
+        for (id, (in_, out)) in self.ins.iter().zip(&self.outs).enumerate() {
+            writeln!(text, "B{}: IN = {}, OUT = {}", id, format_fact(in_), format_fact(out)).unwrap();
+        }
+        text
+    }
+}
+
+/// Solves `analysis` with a worklist, iterating until no fact changes.
+pub fn solve<A: Analysis>(analysis: &A, cfg: &Cfg, quads: &[Quadruple]) -> Solution<A::Fact> {
+    let n = cfg.blocks.len();
+    let forward = analysis.direction() == Direction::Forward;
+    let mut is_entry = vec![false; n];
+    for function in &cfg.functions {
+        is_entry[function.start] = true;
+    }
+
+    let mut ins = vec![analysis.initial(); n];
+    let mut outs = vec![analysis.initial(); n];
+    let mut worklist: VecDeque<usize> = if forward { (0..n).collect() } else { (0..n).rev().collect() };
+    let mut queued = vec![true; n];
+
+    while let Some(id) = worklist.pop_front() {
+        queued[id] = false;
+        let block = &cfg.blocks[id];
+        let (sources, targets) = if forward { (&block.preds, &block.succs) } else { (&block.succs, &block.preds) };
+        let at_boundary = if forward { is_entry[id] } else { block.succs.is_empty() };
+
+        let mut fact = if at_boundary { analysis.boundary() } else { analysis.initial() };
+        for &source in sources {
+            analysis.meet(&mut fact, if forward { &outs[source] } else { &ins[source] });
+        }
+        let result = analysis.transfer(block, quads, &fact);
+
+        let (before, after) = if forward { (&mut ins, &mut outs) } else { (&mut outs, &mut ins) };
+        before[id] = fact;
+        if result != after[id] {
+            after[id] = result;
+            for &target in targets {
+                if !queued[target] {
+                    queued[target] = true;
+                    worklist.push_back(target);
+                }
+            }
+        }
+    }
+    Solution { ins, outs }
+}
+
+/// `{a, b, c}`
+pub fn format_set<T: Display>(set: &BTreeSet<T>) -> String {
+    let items: Vec<_> = set.iter().map(|item| item.to_string()).collect();
+    format!("{{{}}}", items.join(", "))
+}
+
+fn is_variable(operand: &Operand) -> bool {
+    matches!(operand, Operand::Var(_) | Operand::Temp(_))
+}
+
+/// Live variables: the variables and temporaries that may be read before
+/// being assigned again.
+#[derive(Debug, Default)]
+pub struct Liveness;
+
+impl Liveness {
+    /// Updates `live` from after `quad` to before it.
+    pub fn step(live: &mut BTreeSet<Operand>, quad: &Quadruple) {
+        if let Some(def) = quad.def() {
+            live.remove(def);
+        }
+        live.extend(quad.uses().into_iter().filter(|u| is_variable(u)).cloned());
+    }
+}
+
+impl Analysis for Liveness {
+    type Fact = BTreeSet<Operand>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other.iter().cloned());
+    }
+
+    fn transfer(&self, block: &BasicBlock, quads: &[Quadruple], fact: &Self::Fact) -> Self::Fact {
+        let mut live = fact.clone();
+        for quad in quads[block.range()].iter().rev() {
+            Self::step(&mut live, quad);
+        }
+        live
+    }
+}
+
+/// Reaching definitions: the assignments whose value may still be held by
+/// their target. Definitions are identified by the 1-based number of the
+/// quadruple making them.
+#[derive(Debug)]
+pub struct ReachingDefinitions {
+    /// numbers of the quadruples assigning each variable or temporary
+    defs_of: HashMap<Operand, Vec<usize>>,
+}
+
+impl ReachingDefinitions {
+    pub fn new(quads: &[Quadruple]) -> Self {
+        let mut defs_of: HashMap<Operand, Vec<usize>> = HashMap::new();
+        for (i, quad) in quads.iter().enumerate() {
+            if let Some(def) = quad.def() {
+                defs_of.entry(def.clone()).or_default().push(i + 1);
+            }
+        }
+        ReachingDefinitions { defs_of }
+    }
+
+    /// Updates `reaching` from before the quadruple at `index` to after it.
+    pub fn step(&self, reaching: &mut BTreeSet<usize>, index: usize, quad: &Quadruple) {
+        if let Some(def) = quad.def() {
+            for other in &self.defs_of[def] {
+                reaching.remove(other);
+            }
+            reaching.insert(index + 1);
+        }
+    }
+}
+
+impl Analysis for ReachingDefinitions {
+    type Fact = BTreeSet<usize>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other.iter().copied());
+    }
+
+    fn transfer(&self, block: &BasicBlock, quads: &[Quadruple], fact: &Self::Fact) -> Self::Fact {
+        let mut reaching = fact.clone();
+        for index in block.range() {
+            self.step(&mut reaching, index, &quads[index]);
+        }
+        reaching
+    }
+}
+
+/// Use-definition and definition-use chains, with quadruples identified by
+/// their 1-based numbers.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chains {
+    /// the definitions that may reach each use of a variable or temporary
+    pub use_def: BTreeMap<(usize, Operand), BTreeSet<usize>>,
+    /// the uses each definition may reach
+    pub def_use: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl Chains {
+    pub fn build(cfg: &Cfg, quads: &[Quadruple]) -> Chains {
+        let analysis = ReachingDefinitions::new(quads);
+        let solution = solve(&analysis, cfg, quads);
+        let mut chains = Chains::default();
+        for (i, quad) in quads.iter().enumerate() {
+            if quad.def().is_some() {
+                chains.def_use.insert(i + 1, BTreeSet::new());
+            }
+        }
+
+        for block in &cfg.blocks {
+            let mut reaching = solution.ins[block.id].clone();
+            for index in block.range() {
+                let quad = &quads[index];
+                for operand in quad.uses().into_iter().filter(|u| is_variable(u)) {
+                    let defs: BTreeSet<usize> = reaching
+                        .iter()
+                        .copied()
+                        .filter(|&d| quads[d - 1].def() == Some(operand))
+                        .collect();
+                    for &def in &defs {
+                        chains.def_use.get_mut(&def).unwrap().insert(index + 1);
+                    }
+                    chains.use_def.insert((index + 1, operand.clone()), defs);
+                }
+                analysis.step(&mut reaching, index, quad);
+            }
+        }
+        chains
+    }
+}
+
+impl std::fmt::Display for Chains {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "use-def:")?;
+        for ((index, operand), defs) in &self.use_def {
+            writeln!(f, "    {}: {} <- {}", index, operand, format_set(defs))?;
+        }
+        writeln!(f, "def-use:")?;
+        for (def, uses) in &self.def_use {
+            writeln!(f, "    {} -> {}", def, format_set(uses))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse_quadruples;
+    use crate::test_util::compile;
+
+    const LOOP: &str = "int main() {
+        int i = 0;
+        int s = 0;
+        while (i < 5) { s = s + i; i = i + 1; }
+        return s;
+    }";
+    // 1: (func, main, , )      B0
+    // 2: (=, 0, , i)
+    // 3: (=, 0, , s)
+    // 4: (j<, i, 5, 6)         B1
+    // 5: (j, , , 11)           B2
+    // 6: (+, s, i, t1)         B3
+    // 7: (=, t1, , s)
+    // 8: (+, i, 1, t2)
+    // 9: (=, t2, , i)
+    // 10: (j, , , 4)
+    // 11: (return, s, , )      B4
+
+    #[test]
+    fn test_liveness() {
+        let quads = compile(LOOP);
+        let cfg = Cfg::build(&quads);
+        let solution = solve(&Liveness, &cfg, &quads);
+        assert_eq!(solution.format_with(format_set), "\
+B0: IN = {}, OUT = {i, s}
+B1: IN = {i, s}, OUT = {i, s}
+B2: IN = {s}, OUT = {s}
+B3: IN = {i, s}, OUT = {i, s}
+B4: IN = {s}, OUT = {}
+");
+    }
+
+    #[test]
+    fn test_reaching_definitions() {
+        let quads = compile(LOOP);
+        let cfg = Cfg::build(&quads);
+        let solution = solve(&ReachingDefinitions::new(&quads), &cfg, &quads);
+        assert_eq!(solution.format_with(format_set), "\
+B0: IN = {}, OUT = {2, 3}
+B1: IN = {2, 3, 6, 7, 8, 9}, OUT = {2, 3, 6, 7, 8, 9}
+B2: IN = {2, 3, 6, 7, 8, 9}, OUT = {2, 3, 6, 7, 8, 9}
+B3: IN = {2, 3, 6, 7, 8, 9}, OUT = {6, 7, 8, 9}
+B4: IN = {2, 3, 6, 7, 8, 9}, OUT = {2, 3, 6, 7, 8, 9}
+");
+    }
+
+    #[test]
+    fn test_chains() {
+        let quads = compile(LOOP);
+        let chains = Chains::build(&Cfg::build(&quads), &quads);
+        assert_eq!(chains.to_string(), "\
+use-def:
+    4: i <- {2, 9}
+    6: i <- {2, 9}
+    6: s <- {3, 7}
+    7: t1 <- {6}
+    8: i <- {2, 9}
+    9: t2 <- {8}
+    11: s <- {3, 7}
+def-use:
+    2 -> {4, 6, 8}
+    3 -> {6, 11}
+    6 -> {7}
+    7 -> {6, 11}
+    8 -> {9}
+    9 -> {4, 6, 8}
+");
+    }
+
+    #[test]
+    fn test_functions_and_uninitialised_uses() {
+        let quads = parse_quadruples("
+            (func, f, , )
+            (formal, , , a)
+            (return, x, , )
+            (func, main, , )
+            (=, 1, , a)
+            (param, a, , )
+            (call, f, 1, t1)
+            (return, t1, , )
+        ").unwrap();
+        let cfg = Cfg::build(&quads);
+        let chains = Chains::build(&cfg, &quads);
+        assert_eq!(chains.use_def[&(3, Operand::Var("x".to_string()))], BTreeSet::new());
+        assert_eq!(chains.def_use[&2], BTreeSet::new());
+        assert_eq!(chains.def_use[&5], BTreeSet::from([6]));
+
+        let live = solve(&Liveness, &cfg, &quads);
+        assert_eq!(live.ins[0], BTreeSet::from([Operand::Var("x".to_string())]));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeSet;
+
+use crate::cfg::{BlockId, Cfg};
+
+/// Dominator tree and dominance frontiers of every function in a `Cfg`.
+///
+/// A block dominates another if every path from the function entry to the
+/// second one passes through the first. Blocks that cannot be reached from
+/// their function entry take no part.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dominators {
+    /// immediate dominator of every block, `None` for function entries and
+    /// unreachable blocks
+    pub idom: Vec<Option<BlockId>>,
+    /// blocks immediately dominated by each block, in increasing order
+    pub children: Vec<Vec<BlockId>>,
+    /// blocks where the dominance of each block ends
+    pub frontier: Vec<BTreeSet<BlockId>>,
+    /// reachable blocks of each function in reverse postorder, entry first
+    pub order: Vec<Vec<BlockId>>,
+}
+
+impl Dominators {
+    /// Computes immediate dominators with the iterative algorithm of Cooper,
+    /// Harvey and Kennedy, then the frontiers from them.
+    pub fn build(cfg: &Cfg) -> Dominators {
+        let n = cfg.blocks.len();
+        let mut idom: Vec<Option<BlockId>> = vec![None; n];
+        let mut order = Vec::with_capacity(cfg.functions.len());
+        // position of every reachable block in the reverse postorder
+        let mut position = vec![usize::MAX; n];
+
+        for function in &cfg.functions {
+            let entry = function.start;
+            let rpo = reverse_postorder(cfg, entry);
+            for (i, &id) in rpo.iter().enumerate() {
+                position[id] = i;
+            }
+
+            idom[entry] = Some(entry);
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for &id in &rpo[1..] {
+                    let mut new_idom = None;
+                    for &pred in &cfg.blocks[id].preds {
+                        if idom[pred].is_none() {
+                            continue;
+                        }
+                        new_idom = Some(match new_idom {
+                            None => pred,
+                            Some(other) => intersect(&idom, &position, pred, other),
+                        });
+                    }
+                    if new_idom != idom[id] {
+                        idom[id] = new_idom;
+                        changed = true;
+                    }
+                }
+            }
+            idom[entry] = None;
+            order.push(rpo);
+        }
+
+        let mut children = vec![Vec::new(); n];
+        for (id, parent) in idom.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(id);
+            }
+        }
+
+        let mut frontier = vec![BTreeSet::new(); n];
+        for rpo in &order {
+            for &id in rpo {
+                let preds = &cfg.blocks[id].preds;
+                if preds.len() < 2 {
+                    continue;
+                }
+                for &pred in preds.iter().filter(|&&p| position[p] != usize::MAX) {
+                    // walk up from the predecessor until reaching the
+                    // immediate dominator of the join
+                    let mut runner = Some(pred);
+                    while let Some(block) = runner && runner != idom[id] {
+                        frontier[block].insert(id);
+                        runner = idom[block];
+                    }
+                }
+            }
+        }
+
+        Dominators {
+            idom,
+            children,
+            frontier,
+            order,
+        }
+    }
+
+    /// Whether `a` dominates `b`. Every reachable block dominates itself.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        let mut block = Some(b);
+        while let Some(id) = block {
+            if id == a {
+                return true;
+            }
+            block = self.idom[id];
+        }
+        false
+    }
+}
+
+fn reverse_postorder(cfg: &Cfg, entry: BlockId) -> Vec<BlockId> {
+    let mut visited = vec![false; cfg.blocks.len()];
+    let mut postorder = Vec::new();
+    // blocks on the path from the entry, with the next successor to visit
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some((id, next)) = stack.last_mut() {
+        let id = *id;
+        match cfg.blocks[id].succs.get(*next) {
+            Some(&succ) => {
+                *next += 1;
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            }
+            None => {
+                postorder.push(id);
+                stack.pop();
+            }
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// The closest common dominator of `a` and `b`, with `idom` of the entry
+/// pointing at itself.
+fn intersect(idom: &[Option<BlockId>], position: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while position[a] > position[b] {
+            a = idom[a].unwrap();
+        }
+        while position[b] > position[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+impl std::fmt::Display for Dominators {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let blocks = |ids: &mut dyn Iterator<Item = &BlockId>| {
+            let names: Vec<_> = ids.map(|id| format!("B{}", id)).collect();
+            format!("{{{}}}", names.join(", "))
+        };
+        for rpo in &self.order {
+            let mut reachable = rpo.clone();
+            reachable.sort_unstable();
+            for id in reachable {
+                let idom = match self.idom[id] {
+                    Some(parent) => format!("B{}", parent),
+                    None => "-".to_string(),
+                };
+                writeln!(
+                    f,
+                    "B{}: idom = {}, children = {}, DF = {}",
+                    id,
+                    idom,
+                    blocks(&mut self.children[id].iter()),
+                    blocks(&mut self.frontier[id].iter()),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse_quadruples;
+    use crate::test_util::compile;
+
+    #[test]
+    fn test_if_else() {
+        // blocks as in the CFG tests: B0 branches to B2 and, through B1, to
+        // B3, which both lead to B4
+        let quads = compile("int main() { int x = 1; if (x > 0) { x = 2; } else { x = 3; } return x; }");
+        let dominators = Dominators::build(&Cfg::build(&quads));
+        assert_eq!(dominators.to_string(), "\
+B0: idom = -, children = {B1, B2, B4}, DF = {}
+B1: idom = B0, children = {B3}, DF = {B4}
+B2: idom = B0, children = {}, DF = {B4}
+B3: idom = B1, children = {}, DF = {B4}
+B4: idom = B0, children = {}, DF = {}
+");
+        assert!(dominators.dominates(0, 3));
+        assert!(dominators.dominates(4, 4));
+        assert!(!dominators.dominates(2, 4));
+    }
+
+    #[test]
+    fn test_loop() {
+        let quads = compile("int main() {
+            int i = 0;
+            while (i < 5) { i = i + 1; }
+            return i;
+        }");
+        // B0 entry, B1 header, B2 exit jump, B3 body, B4 return
+        let dominators = Dominators::build(&Cfg::build(&quads));
+        assert_eq!(dominators.idom, vec![None, Some(0), Some(1), Some(1), Some(2)]);
+        assert_eq!(dominators.frontier[3], BTreeSet::from([1]));
+        assert_eq!(dominators.frontier[1], BTreeSet::from([1]));
+        assert_eq!(dominators.order, vec![vec![0, 1, 3, 2, 4]]);
+    }
+
+    #[test]
+    fn test_functions_and_unreachable_blocks() {
+        let quads = parse_quadruples("
+            (func, f, , )
+            (return, 1, , )
+            (return, 2, , )
+            (func, main, , )
+            (return, 0, , )
+        ").unwrap();
+        let dominators = Dominators::build(&Cfg::build(&quads));
+        assert_eq!(dominators.idom, vec![None, None, None]);
+        assert_eq!(dominators.order, vec![vec![0], vec![2]]);
+        assert!(!dominators.dominates(0, 1));
+        assert_eq!(dominators.to_string(), "\
+B0: idom = -, children = {}, DF = {}
+B2: idom = -, children = {}, DF = {}
+");
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::{Block, Expr, Function, Program, Stmt};
+use crate::codegen::Location;
+use crate::token::Span;
+
+/// Default number of statements and expressions evaluated before giving up.
+pub const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+
+/// Calls nested deeper than this fail with `StackOverflow`, same as in the
+/// quadruple interpreter.
+pub use crate::interpreter::MAX_CALL_DEPTH;
+
+/// Evaluation recurses on the host stack, so it runs on its own thread with
+/// room for `MAX_CALL_DEPTH` nested calls. Unoptimised builds take about
+/// 16 KiB per call.
+const EVAL_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    StepLimitExceeded { limit: usize },
+    StackOverflow { location: Location },
+    UndeclaredVariable { name: String, location: Location },
+    UninitializedVariable { name: String, location: Location },
+    UndefinedFunction { name: String, location: Option<Location> },
+    ArgumentCountMismatch { name: String, expected: usize, found: usize, location: Option<Location> },
+    DivisionByZero { location: Location },
+    /// The AST contains an error placeholder left by parser recovery.
+    SyntaxError { location: Location },
+    /// The thread evaluation runs on could not be started.
+    ThreadSpawnFailed { message: String },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::StepLimitExceeded { limit } => write!(f, "Step limit of {} exceeded", limit),
+            EvalError::StackOverflow { location } => {
+                write!(f, "Call stack overflow at line {}, column {}", location.line, location.column)
+            }
+            EvalError::UndeclaredVariable { name, location } => {
+                write!(f, "Undeclared variable '{}' at line {}, column {}", name, location.line, location.column)
+            }
+            EvalError::UninitializedVariable { name, location } => {
+                write!(f, "Read of uninitialized variable '{}' at line {}, column {}", name, location.line, location.column)
+            }
+            EvalError::UndefinedFunction { name, location } => {
+                write!(f, "Undefined function '{}'", name)?;
+                if let Some(loc) = location {
+                    write!(f, " at line {}, column {}", loc.line, loc.column)?;
+                }
+                Ok(())
+            }
+            EvalError::ArgumentCountMismatch { name, expected, found, location } => {
+                write!(f, "Function '{}' takes {} argument(s) but {} were supplied", name, expected, found)?;
+                if let Some(loc) = location {
+                    write!(f, " at line {}, column {}", loc.line, loc.column)?;
+                }
+                Ok(())
+            }
+            EvalError::DivisionByZero { location } => {
+                write!(f, "Division by zero at line {}, column {}", location.line, location.column)
+            }
+            EvalError::SyntaxError { location } => {
+                write!(f, "Cannot evaluate syntax error at line {}, column {}", location.line, location.column)
+            }
+            EvalError::ThreadSpawnFailed { message } => write!(f, "Cannot start the evaluator thread: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Evaluates a program directly on the AST. This is the reference
+/// semantics the generated quadruples are checked against: i32 values with
+/// wrapping arithmetic, 0/1 results for relational and logical operators,
+/// short-circuit `&&` and `||`, and lexical block scoping where parameters
+/// share the scope of the function body. Falling off the end of a function
+/// returns 0.
+#[derive(Debug)]
+pub struct Evaluator<'a> {
+    pub program: &'a Program,
+    pub step_limit: usize,
+    /// number of steps taken by the last evaluation
+    pub steps: usize,
+    /// innermost scope last, `None` until the variable is assigned
+    scopes: Vec<HashMap<String, Option<i32>>>,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Evaluator {
+            program,
+            step_limit: DEFAULT_STEP_LIMIT,
+            steps: 0,
+            scopes: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// Runs `main` and returns its return value.
+    pub fn run(&mut self) -> Result<i32, EvalError> {
+        self.call("main", &[])
+    }
+
+    /// Calls the function `name` with `args` and returns its return value.
+    pub fn call(&mut self, name: &str, args: &[i32]) -> Result<i32, EvalError> {
+        self.steps = 0;
+        self.depth = 0;
+        self.scopes.clear();
+        let func = self.find_function(name, None)?;
+
+        std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(EVAL_STACK_SIZE)
+                .spawn_scoped(scope, || self.call_function(func, args.to_vec(), None))
+                .map_err(|e| EvalError::ThreadSpawnFailed { message: e.to_string() })?
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e))
+        })
+    }
+
+    fn find_function(&self, name: &str, span: Option<Span>) -> Result<&'a Function, EvalError> {
+        self.program.functions.iter().find(|f| f.name == name).ok_or_else(|| EvalError::UndefinedFunction {
+            name: name.to_string(),
+            location: span.map(Location::from),
+        })
+    }
+
+    fn call_function(&mut self, func: &'a Function, args: Vec<i32>, span: Option<Span>) -> Result<i32, EvalError> {
+        if func.params.len() != args.len() {
+            return Err(EvalError::ArgumentCountMismatch {
+                name: func.name.clone(),
+                expected: func.params.len(),
+                found: args.len(),
+                location: span.map(Location::from),
+            });
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(EvalError::StackOverflow {
+                location: span.unwrap_or(func.span).into(),
+            });
+        }
+
+        // the callee cannot see the caller's variables
+        let saved = std::mem::take(&mut self.scopes);
+        self.depth += 1;
+
+        let mut scope = HashMap::new();
+        for (param, value) in func.params.iter().zip(args) {
+            scope.insert(param.name.clone(), Some(value));
+        }
+        self.scopes.push(scope);
+        let result = self.exec_stmts(&func.block.stmts);
+
+        self.depth -= 1;
+        self.scopes = saved;
+        Ok(result?.unwrap_or(0))
+    }
+
+    fn step(&mut self) -> Result<(), EvalError> {
+        self.steps += 1;
+        if self.steps > self.step_limit {
+            return Err(EvalError::StepLimitExceeded { limit: self.step_limit });
+        }
+        Ok(())
+    }
+
+    fn exec_block(&mut self, block: &Block) -> Result<Option<i32>, EvalError> {
+        self.scopes.push(HashMap::new());
+        let result = self.exec_stmts(&block.stmts);
+        self.scopes.pop();
+        result
+    }
+
+    /// Executes statements in the current scope. Returns `Some(value)` once
+    /// a `return` has been executed.
+    fn exec_stmts(&mut self, stmts: &[Stmt]) -> Result<Option<i32>, EvalError> {
+        for stmt in stmts {
+            if let Some(value) = self.exec_stmt(stmt)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    fn exec_stmt(&mut self, stmt: &Stmt) -> Result<Option<i32>, EvalError> {
+        self.step()?;
+        match stmt {
+            Stmt::ReturnStmt(expr) => Ok(Some(self.eval(expr)?)),
+            Stmt::IfStmt { cond, if_block, else_stmt } => {
+                if self.eval(cond)? != 0 {
+                    self.exec_block(if_block)
+                }
+                else if let Some(else_blk) = else_stmt {
+                    self.exec_block(else_blk)
+                }
+                else {
+                    Ok(None)
+                }
+            }
+            Stmt::WhileStmt { cond, block } => {
+                while self.eval(cond)? != 0 {
+                    if let Some(value) = self.exec_block(block)? {
+                        return Ok(Some(value));
+                    }
+                    self.step()?;
+                }
+                Ok(None)
+            }
+            Stmt::AssignmentStmt { lval, rval, span } => {
+                let value = self.eval(rval)?;
+                let slot = self.scopes
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.get_mut(lval))
+                    .ok_or_else(|| EvalError::UndeclaredVariable {
+                        name: lval.clone(),
+                        location: (*span).into(),
+                    })?;
+                *slot = Some(value);
+                Ok(None)
+            }
+            Stmt::DeclareStmt { ident, rval, .. } => {
+                // the initialiser cannot see the variable being declared
+                let value = match rval {
+                    Some(expr) => Some(self.eval(expr)?),
+                    None => None,
+                };
+                self.scopes.last_mut().unwrap().insert(ident.clone(), value);
+                Ok(None)
+            }
+            Stmt::Error(span) => Err(EvalError::SyntaxError { location: (*span).into() }),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<i32, EvalError> {
+        self.step()?;
+        match expr {
+            Expr::Number(n, _) => Ok(*n),
+            Expr::Var(name, span) => {
+                match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+                    Some(Some(value)) => Ok(*value),
+                    Some(None) => Err(EvalError::UninitializedVariable {
+                        name: name.clone(),
+                        location: (*span).into(),
+                    }),
+                    None => Err(EvalError::UndeclaredVariable {
+                        name: name.clone(),
+                        location: (*span).into(),
+                    }),
+                }
+            }
+            Expr::UnaryExpr { op, operand, .. } => {
+                let v = self.eval(operand)?;
+                Ok(match op.as_str() {
+                    "-" => v.wrapping_neg(),
+                    "!" => (v == 0) as i32,
+                    _ => v,
+                })
+            }
+            Expr::BinaryExpr { op, lhs, rhs, .. } if op == "&&" => {
+                Ok((self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i32)
+            }
+            Expr::BinaryExpr { op, lhs, rhs, .. } if op == "||" => {
+                Ok((self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i32)
+            }
+            Expr::BinaryExpr { op, lhs, rhs, span } => {
+                let l = self.eval(lhs)?;
+                let r = self.eval(rhs)?;
+                Ok(match op.as_str() {
+                    "+" => l.wrapping_add(r),
+                    "-" => l.wrapping_sub(r),
+                    "*" => l.wrapping_mul(r),
+                    "/" | "%" if r == 0 => return Err(EvalError::DivisionByZero { location: (*span).into() }),
+                    "/" => l.wrapping_div(r),
+                    "%" => l.wrapping_rem(r),
+                    ">" => (l > r) as i32,
+                    "<" => (l < r) as i32,
+                    ">=" => (l >= r) as i32,
+                    "<=" => (l <= r) as i32,
+                    "==" => (l == r) as i32,
+                    _ => (l != r) as i32,
+                })
+            }
+            Expr::Call { name, args, span } => {
+                let func = self.find_function(name, Some(*span))?;
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                self.call_function(func, values, Some(*span))
+            }
+            Expr::Error(span) => Err(EvalError::SyntaxError { location: (*span).into() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::CodeGenerator;
+    use crate::interpreter::Interpreter;
+    use crate::test_util::parse;
+
+    fn eval(input: &str) -> Result<i32, EvalError> {
+        Evaluator::new(&parse(input)).run()
+    }
+
+    /// Checks that the generated quadruples compute what the AST means.
+    fn assert_agrees(input: &str) -> i32 {
+        let program = parse(input);
+        let expected = Evaluator::new(&program).run().unwrap();
+
+        let mut codegen = CodeGenerator::new();
+        codegen.generate(&program).unwrap();
+        let actual = Interpreter::new(&codegen.quadruples).run().unwrap();
+
+        assert_eq!(actual, expected, "quadruples disagree with the AST for:\n{}", input);
+        expected
+    }
+
+    #[test]
+    fn test_eval_basic() {
+        assert_eq!(eval("int main() { int x = 5; x = x + 1; return x * -2; }"), Ok(-12));
+        assert_eq!(eval("int main() { int x = 1; }"), Ok(0));
+        assert_eq!(eval("int main() { return 1 || 1 / 0; }"), Ok(1));
+    }
+
+    #[test]
+    fn test_eval_scopes() {
+        let program = "int main() {
+            int x = 1;
+            if (x) { int x = 2; x = x + 10; }
+            if (x) { int y = x; x = y + 100; }
+            return x;
+        }";
+        assert_eq!(eval(program), Ok(101));
+        assert!(matches!(eval("int main() { if (1) { int y = 1; } if (1) { y = 2; } return 0; }"),
+            Err(EvalError::UndeclaredVariable { ref name, .. }) if name == "y"));
+        assert!(matches!(eval("int main() { int z; return z; }"),
+            Err(EvalError::UninitializedVariable { ref name, .. }) if name == "z"));
+    }
+
+    #[test]
+    fn test_eval_calls() {
+        let program = parse("int fib(int n) { if (n < 2) { return n; } return fib(n - 1) + fib(n - 2); }
+            int main() { return fib(10); }");
+        let mut evaluator = Evaluator::new(&program);
+        assert_eq!(evaluator.run(), Ok(55));
+        assert_eq!(evaluator.call("fib", &[12]), Ok(144));
+        assert!(matches!(evaluator.call("fib", &[]), Err(EvalError::ArgumentCountMismatch { .. })));
+        assert!(matches!(evaluator.call("nope", &[]), Err(EvalError::UndefinedFunction { .. })));
+    }
+
+    #[test]
+    fn test_eval_limits() {
+        let program = parse("int main() { while (1) { } return 0; }");
+        let mut evaluator = Evaluator::new(&program);
+        evaluator.step_limit = 50;
+        assert_eq!(evaluator.run(), Err(EvalError::StepLimitExceeded { limit: 50 }));
+
+        assert!(matches!(eval("int f() { return f(); } int main() { return f(); }"),
+            Err(EvalError::StackOverflow { .. })));
+        // as deep as the interpreter allows
+        let deep = "int f(int n) { if (n == 0) { return 0; } return (n % 2 + f(n - 1)) * 1; }
+            int main() { return f(N); }";
+        assert_eq!(assert_agrees(&deep.replace("N", &(MAX_CALL_DEPTH - 2).to_string())), 4999);
+        assert!(matches!(eval("int main() { int x = 0; return 3 % x; }"),
+            Err(EvalError::DivisionByZero { .. })));
+    }
+
+    #[test]
+    fn test_quadruples_agree() {
+        assert_eq!(assert_agrees("int main() {
+            int i = 0;
+            int s = 0;
+            while (i < 20) {
+                if (i % 3 == 0 || i % 5 == 0 && !(i > 15)) { s = s + i; }
+                else { s = s - 1; }
+                i = i + 1;
+            }
+            return s;
+        }"), 67);
+        assert_agrees("int gcd(int a, int b) { while (b != 0) { int t = a % b; a = b; b = t; } return a; }
+            int main() { return gcd(1071, 462) * 1000 + gcd(17, 5); }");
+        assert_agrees("int main() { int a = 3; int b = (a > 2) + (a && 0) * 10 + (0 || a) * 100; return -b; }");
+        assert_eq!(assert_agrees("int main() { int x = 1; if (x) { int x = 2; x = x * 10; } return x; }"), 1);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ir::{Opcode, Operand, Quadruple};
+
+/// Default number of quadruples executed before giving up.
+pub const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+
+/// Calls nested deeper than this fail with `StackOverflow`.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// More than `limit` quadruples were executed, most likely an infinite
+    /// loop.
+    StepLimitExceeded { limit: usize },
+    StackOverflow { index: usize },
+    UndefinedFunction { name: String, index: Option<usize> },
+    UninitializedVariable { name: String, index: usize },
+    DivisionByZero { index: usize },
+    InvalidJumpTarget { target: String, index: usize },
+    /// A call passed a different number of `param`s than the callee has
+    /// `formal`s.
+    ArgumentCountMismatch { name: String, index: usize },
+    /// An operand that does not hold a value, such as a label, was read.
+    InvalidOperand { operand: String, index: usize },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // quadruples are numbered from 1 in listings
+        match self {
+            RuntimeError::StepLimitExceeded { limit } => {
+                write!(f, "Step limit of {} exceeded", limit)
+            }
+            RuntimeError::StackOverflow { index } => {
+                write!(f, "Call stack overflow at quadruple {}", index + 1)
+            }
+            RuntimeError::UndefinedFunction { name, index: Some(index) } => {
+                write!(f, "Call to undefined function '{}' at quadruple {}", name, index + 1)
+            }
+            RuntimeError::UndefinedFunction { name, index: None } => {
+                write!(f, "Undefined function '{}'", name)
+            }
+            RuntimeError::UninitializedVariable { name, index } => {
+                write!(f, "Read of uninitialized '{}' at quadruple {}", name, index + 1)
+            }
+            RuntimeError::DivisionByZero { index } => {
+                write!(f, "Division by zero at quadruple {}", index + 1)
+            }
+            RuntimeError::InvalidJumpTarget { target, index } => {
+                write!(f, "Invalid jump target '{}' at quadruple {}", target, index + 1)
+            }
+            RuntimeError::ArgumentCountMismatch { name, index } => {
+                write!(f, "Wrong number of arguments for '{}' at quadruple {}", name, index + 1)
+            }
+            RuntimeError::InvalidOperand { operand, index } => {
+                write!(f, "Invalid operand '{}' at quadruple {}", operand, index + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[derive(Debug)]
+struct Frame {
+    /// variables and temporaries of the function
+    vars: HashMap<Operand, i32>,
+    /// arguments passed by the caller, consumed by `formal`
+    args: Vec<i32>,
+    /// index of the call in the caller, `None` for the outermost frame
+    call_index: Option<usize>,
+}
+
+/// Executes the quadruples produced by `CodeGenerator`.
+///
+/// Every value is an i32 with wrapping arithmetic. Variables and
+/// temporaries are local to the current function. Jump targets are 1-based
+/// quadruple numbers, as printed by `print_quadruples`. Running into the
+/// next `func` or past the last quadruple returns 0 from the current
+/// function.
+#[derive(Debug)]
+pub struct Interpreter<'a> {
+    pub quadruples: &'a [Quadruple],
+    pub step_limit: usize,
+    /// number of quadruples executed by the last run
+    pub steps: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(quadruples: &'a [Quadruple]) -> Self {
+        Interpreter {
+            quadruples,
+            step_limit: DEFAULT_STEP_LIMIT,
+            steps: 0,
+        }
+    }
+
+    /// Runs `main` and returns its return value.
+    pub fn run(&mut self) -> Result<i32, RuntimeError> {
+        self.call("main", &[])
+    }
+
+    /// Calls the function `name` with `args` and returns its return value.
+    pub fn call(&mut self, name: &str, args: &[i32]) -> Result<i32, RuntimeError> {
+        self.steps = 0;
+        let entry = self.find_function(name).ok_or_else(|| RuntimeError::UndefinedFunction {
+            name: name.to_string(),
+            index: None,
+        })?;
+
+        let mut stack = vec![Frame {
+            vars: HashMap::new(),
+            args: args.iter().rev().copied().collect(),
+            call_index: None,
+        }];
+        let mut params: Vec<i32> = Vec::new();
+        let mut pc = entry + 1;
+
+        loop {
+            let Some(quad) = self.quadruples.get(pc).filter(|q| q.op != Opcode::Func) else {
+                // fell off the end of the function
+                match self.do_return(&mut stack, 0) {
+                    Some(next) => {
+                        pc = next;
+                        continue;
+                    }
+                    None => return Ok(0),
+                }
+            };
+
+            self.steps += 1;
+            if self.steps > self.step_limit {
+                return Err(RuntimeError::StepLimitExceeded { limit: self.step_limit });
+            }
+
+            let index = pc;
+            pc += 1;
+            let frame = stack.last_mut().unwrap();
+
+            match quad.op {
+                Opcode::Jump => pc = self.jump_target(quad, index)?,
+                Opcode::Assign => {
+                    let v = Self::value(frame, &quad.arg1, index)?;
+                    frame.vars.insert(quad.result.clone(), v);
+                }
+                Opcode::Neg => {
+                    let v = Self::value(frame, &quad.arg1, index)?;
+                    frame.vars.insert(quad.result.clone(), v.wrapping_neg());
+                }
+                Opcode::Not => {
+                    let v = Self::value(frame, &quad.arg1, index)?;
+                    frame.vars.insert(quad.result.clone(), (v == 0) as i32);
+                }
+                Opcode::Formal => {
+                    let v = frame.args.pop().ok_or_else(|| RuntimeError::ArgumentCountMismatch {
+                        name: self.function_at(index),
+                        index,
+                    })?;
+                    frame.vars.insert(quad.result.clone(), v);
+                }
+                Opcode::Param => {
+                    params.push(Self::value(frame, &quad.arg1, index)?);
+                }
+                Opcode::Call => {
+                    let name = quad.arg1.to_string();
+                    let entry = self.find_function(&name).ok_or(RuntimeError::UndefinedFunction {
+                        name,
+                        index: Some(index),
+                    })?;
+                    if stack.len() >= MAX_CALL_DEPTH {
+                        return Err(RuntimeError::StackOverflow { index });
+                    }
+                    let argc = match quad.arg2 {
+                        Operand::Const(n) => (n.max(0) as usize).min(params.len()),
+                        _ => params.len(),
+                    };
+                    let mut args = params.split_off(params.len() - argc);
+                    args.reverse();
+                    stack.push(Frame {
+                        vars: HashMap::new(),
+                        args,
+                        call_index: Some(index),
+                    });
+                    pc = entry + 1;
+                }
+                Opcode::Return => {
+                    let v = if quad.arg1.is_empty() { 0 } else { Self::value(frame, &quad.arg1, index)? };
+                    match self.do_return(&mut stack, v) {
+                        Some(next) => pc = next,
+                        None => return Ok(v),
+                    }
+                }
+                Opcode::Binary(op) => {
+                    let a = Self::value(frame, &quad.arg1, index)?;
+                    let b = Self::value(frame, &quad.arg2, index)?;
+                    let v = op.eval(a, b).ok_or(RuntimeError::DivisionByZero { index })?;
+                    frame.vars.insert(quad.result.clone(), v);
+                }
+                Opcode::CondJump(op) => {
+                    let a = Self::value(frame, &quad.arg1, index)?;
+                    let b = Self::value(frame, &quad.arg2, index)?;
+                    if op.holds(a, b) {
+                        pc = self.jump_target(quad, index)?;
+                    }
+                }
+                // stops the lookup of `quad` above
+                Opcode::Func => unreachable!(),
+            }
+        }
+    }
+
+    /// Pops the current frame and stores `value` into the result of the
+    /// call that created it. Returns where the caller continues, or `None`
+    /// if the outermost function returned.
+    fn do_return(&self, stack: &mut Vec<Frame>, value: i32) -> Option<usize> {
+        let frame = stack.pop()?;
+        let call_index = frame.call_index?;
+        let caller = stack.last_mut()?;
+        caller.vars.insert(self.quadruples[call_index].result.clone(), value);
+        Some(call_index + 1)
+    }
+
+    fn find_function(&self, name: &str) -> Option<usize> {
+        self.quadruples
+            .iter()
+            .position(|q| q.op == Opcode::Func && matches!(&q.arg1, Operand::Func(f) if f == name))
+    }
+
+    /// Name of the function containing the quadruple at `index`.
+    fn function_at(&self, index: usize) -> String {
+        self.quadruples[..=index]
+            .iter()
+            .rev()
+            .find(|q| q.op == Opcode::Func)
+            .map_or(String::new(), |q| q.arg1.to_string())
+    }
+
+    fn jump_target(&self, quad: &Quadruple, index: usize) -> Result<usize, RuntimeError> {
+        match quad.result {
+            Operand::Label(target) if target >= 1 && target <= self.quadruples.len() + 1 => Ok(target - 1),
+            _ => Err(RuntimeError::InvalidJumpTarget {
+                target: quad.result.to_string(),
+                index,
+            }),
+        }
+    }
+
+    fn value(frame: &Frame, operand: &Operand, index: usize) -> Result<i32, RuntimeError> {
+        match operand {
+            Operand::Const(n) => Ok(*n),
+            Operand::Var(_) | Operand::Temp(_) => {
+                frame.vars.get(operand).copied().ok_or_else(|| RuntimeError::UninitializedVariable {
+                    name: operand.to_string(),
+                    index,
+                })
+            }
+            _ => Err(RuntimeError::InvalidOperand {
+                operand: operand.to_string(),
+                index,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::compile;
+
+    fn run(input: &str) -> Result<i32, RuntimeError> {
+        Interpreter::new(&compile(input)).run()
+    }
+
+    #[test]
+    fn test_run_arithmetic() {
+        assert_eq!(run("int main() { int x = 7; return -x * 3 + x / 2 - x % 4; }"), Ok(-21));
+        assert_eq!(run("int main() { int x = -2147483648; return x / -1; }"), Ok(i32::MIN));
+        assert_eq!(run("int main() { int x = 2147483647; return x + 1; }"), Ok(i32::MIN));
+    }
+
+    #[test]
+    fn test_run_calls() {
+        let quads = compile("int sub(int a, int b) { return a - b; }
+            int fact(int n) { if (n <= 1) { return 1; } return n * fact(n - 1); }
+            int main() { return sub(fact(5), sub(20, 2)); }");
+        let mut interp = Interpreter::new(&quads);
+        assert_eq!(interp.run(), Ok(102));
+        assert_eq!(interp.call("sub", &[1, 10]), Ok(-9));
+        assert_eq!(interp.call("fact", &[6]), Ok(720));
+    }
+
+    #[test]
+    fn test_run_implicit_return() {
+        assert_eq!(run("int f() { int x = 3; } int main() { return f() + 1; }"), Ok(1));
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let quads = compile("int main() { int x = 0; while (x == 0) { x = 0; } return x; }");
+        let mut interp = Interpreter::new(&quads);
+        interp.step_limit = 100;
+        assert_eq!(interp.run(), Err(RuntimeError::StepLimitExceeded { limit: 100 }));
+        assert_eq!(interp.steps, 101);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let err = run("int f(int n) { return f(n + 1); } int main() { return f(0); }").unwrap_err();
+        assert!(matches!(err, RuntimeError::StackOverflow { .. }));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert_eq!(run("int main() { int x = 0; return 1 / x; }"), Err(RuntimeError::DivisionByZero { index: 2 }));
+        assert_eq!(run("int main() { int x; return x; }"), Err(RuntimeError::UninitializedVariable {
+            name: "x".to_string(),
+            index: 1,
+        }));
+        assert_eq!(run("int f() { return 0; }"), Err(RuntimeError::UndefinedFunction {
+            name: "main".to_string(),
+            index: None,
+        }));
+
+        let quads = vec![
+            Quadruple::new(Opcode::Func, Operand::Func("main".to_string()), Operand::Empty, Operand::Empty),
+            Quadruple::new(Opcode::Jump, Operand::Empty, Operand::Empty, Operand::Label(9)),
+        ];
+        assert_eq!(Interpreter::new(&quads).run(), Err(RuntimeError::InvalidJumpTarget {
+            target: "9".to_string(),
+            index: 1,
+        }));
+
+        let quads = vec![
+            Quadruple::new(Opcode::Func, Operand::Func("main".to_string()), Operand::Empty, Operand::Empty),
+            Quadruple::new(Opcode::Return, Operand::Label(1), Operand::Empty, Operand::Empty),
+        ];
+        assert_eq!(Interpreter::new(&quads).run(), Err(RuntimeError::InvalidOperand {
+            operand: "1".to_string(),
+            index: 1,
+        }));
+    }
+}
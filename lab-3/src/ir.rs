@@ -0,0 +1,329 @@
+use std::fmt;
+
+/// The textual form of quadruples, one per line:
+///
+/// ```text
+/// 1: (func, main, , )
+/// 2: (=, 10, , x)
+/// 3: (j>, x, 0, 5)        // comment
+/// 4: (j, , , 7)
+/// (-, x, 1, t1)
+/// ```
+///
+/// The `N:` prefix is optional; when present it must equal the position of
+/// the quadruple in the list, which is what jump targets refer to. The
+/// opcode is written as by `Opcode`'s `Display`, and each operand is
+/// interpreted according to its position: jump targets are 1-based
+/// quadruple numbers, `func` and `call` name a function, `call` takes an
+/// argument count, and every other operand is an integer constant, a
+/// temporary `t<n>` or a variable name such as `x` or `x.1`. An empty
+/// operand is left blank. Text after `//` and blank lines are ignored.
+///
+/// `parse_quadruples(&format_quadruples(quads))` gives back `quads`.
+pub mod text;
+
+pub use text::{format_quadruples, parse_quadruples};
+
+/// Relational operators, shared by comparisons that produce a value and by
+/// conditional jumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelOp {
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl RelOp {
+    pub fn from_symbol(symbol: &str) -> Option<RelOp> {
+        match symbol {
+            ">" => Some(RelOp::Gt),
+            "<" => Some(RelOp::Lt),
+            ">=" => Some(RelOp::Ge),
+            "<=" => Some(RelOp::Le),
+            "==" => Some(RelOp::Eq),
+            "!=" => Some(RelOp::Ne),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            RelOp::Gt => ">",
+            RelOp::Lt => "<",
+            RelOp::Ge => ">=",
+            RelOp::Le => "<=",
+            RelOp::Eq => "==",
+            RelOp::Ne => "!=",
+        }
+    }
+
+    pub fn holds(self, a: i32, b: i32) -> bool {
+        match self {
+            RelOp::Gt => a > b,
+            RelOp::Lt => a < b,
+            RelOp::Ge => a >= b,
+            RelOp::Le => a <= b,
+            RelOp::Eq => a == b,
+            RelOp::Ne => a != b,
+        }
+    }
+}
+
+/// Operators of quadruples of the form `(op, a, b, t)`, computing `t = a op b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    /// comparison yielding 1 or 0
+    Rel(RelOp),
+}
+
+impl BinOp {
+    pub fn from_symbol(symbol: &str) -> Option<BinOp> {
+        match symbol {
+            "+" => Some(BinOp::Add),
+            "-" => Some(BinOp::Sub),
+            "*" => Some(BinOp::Mul),
+            "/" => Some(BinOp::Div),
+            "%" => Some(BinOp::Mod),
+            _ => RelOp::from_symbol(symbol).map(BinOp::Rel),
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Rel(op) => op.symbol(),
+        }
+    }
+
+    /// Applies the operator with the wrapping semantics of i32. Returns
+    /// `None` for a division by zero.
+    pub fn eval(self, a: i32, b: i32) -> Option<i32> {
+        match self {
+            BinOp::Add => Some(a.wrapping_add(b)),
+            BinOp::Sub => Some(a.wrapping_sub(b)),
+            BinOp::Mul => Some(a.wrapping_mul(b)),
+            BinOp::Div => (b != 0).then(|| a.wrapping_div(b)),
+            BinOp::Mod => (b != 0).then(|| a.wrapping_rem(b)),
+            BinOp::Rel(op) => Some(op.holds(a, b) as i32),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    /// `(op, a, b, t)`: t = a op b
+    Binary(BinOp),
+    /// `(uminus, a, , t)`: t = -a
+    Neg,
+    /// `(not, a, , t)`: t = a == 0
+    Not,
+    /// `(=, a, , x)`: x = a
+    Assign,
+    /// `(j, , , L)`: goto L
+    Jump,
+    /// `(j<op>, a, b, L)`: if a op b goto L
+    CondJump(RelOp),
+    /// `(param, a, , )`: pass a to the next call
+    Param,
+    /// `(call, f, n, t)`: t = f applied to the last n params
+    Call,
+    /// `(return, a, , )`
+    Return,
+    /// `(func, f, , )`: start of function f
+    Func,
+    /// `(formal, , , x)`: bind the next argument to parameter x
+    Formal,
+}
+
+impl Opcode {
+    /// Inverse of `Display`.
+    pub fn from_name(name: &str) -> Option<Opcode> {
+        match name {
+            "uminus" => Some(Opcode::Neg),
+            "not" => Some(Opcode::Not),
+            "=" => Some(Opcode::Assign),
+            "j" => Some(Opcode::Jump),
+            "param" => Some(Opcode::Param),
+            "call" => Some(Opcode::Call),
+            "return" => Some(Opcode::Return),
+            "func" => Some(Opcode::Func),
+            "formal" => Some(Opcode::Formal),
+            _ => match name.strip_prefix('j') {
+                Some(rel) => RelOp::from_symbol(rel).map(Opcode::CondJump),
+                None => BinOp::from_symbol(name).map(Opcode::Binary),
+            },
+        }
+    }
+
+    pub fn is_jump(self) -> bool {
+        matches!(self, Opcode::Jump | Opcode::CondJump(_))
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Opcode::Binary(op) => write!(f, "{}", op.symbol()),
+            Opcode::Neg => write!(f, "uminus"),
+            Opcode::Not => write!(f, "not"),
+            Opcode::Assign => write!(f, "="),
+            Opcode::Jump => write!(f, "j"),
+            Opcode::CondJump(op) => write!(f, "j{}", op.symbol()),
+            Opcode::Param => write!(f, "param"),
+            Opcode::Call => write!(f, "call"),
+            Opcode::Return => write!(f, "return"),
+            Opcode::Func => write!(f, "func"),
+            Opcode::Formal => write!(f, "formal"),
+        }
+    }
+}
+
+/// The number `n` if `name` is spelled like the temporary `t<n>`.
+pub fn temp_index(name: &str) -> Option<usize> {
+    let digits = name.strip_prefix('t')?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Operand {
+    /// a source variable, by its IR name
+    Var(String),
+    /// the compiler temporary `t<n>`
+    Temp(usize),
+    Const(i32),
+    /// a 1-based quadruple number, the target of a jump
+    Label(usize),
+    /// the function named by `func` and `call`
+    Func(String),
+    Empty,
+}
+
+impl Operand {
+    pub fn is_empty(&self) -> bool {
+        *self == Operand::Empty
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Var(name) | Operand::Func(name) => write!(f, "{}", name),
+            Operand::Temp(n) => write!(f, "t{}", n),
+            Operand::Const(n) => write!(f, "{}", n),
+            Operand::Label(n) => write!(f, "{}", n),
+            Operand::Empty => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quadruple {
+    pub op: Opcode,
+    pub arg1: Operand,
+    pub arg2: Operand,
+    pub result: Operand,
+}
+
+impl Quadruple {
+    pub fn new(op: Opcode, arg1: Operand, arg2: Operand, result: Operand) -> Self {
+        Quadruple {
+            op,
+            arg1,
+            arg2,
+            result,
+        }
+    }
+
+    /// Operands whose values the quadruple reads. Function names, argument
+    /// counts and jump targets are not values.
+    pub fn uses(&self) -> Vec<&Operand> {
+        let uses = match self.op {
+            Opcode::Binary(_) | Opcode::CondJump(_) => vec![&self.arg1, &self.arg2],
+            Opcode::Neg | Opcode::Not | Opcode::Assign | Opcode::Param | Opcode::Return => vec![&self.arg1],
+            _ => vec![],
+        };
+        uses.into_iter().filter(|operand| !operand.is_empty()).collect()
+    }
+
+    pub fn uses_mut(&mut self) -> Vec<&mut Operand> {
+        let uses = match self.op {
+            Opcode::Binary(_) | Opcode::CondJump(_) => vec![&mut self.arg1, &mut self.arg2],
+            Opcode::Neg | Opcode::Not | Opcode::Assign | Opcode::Param | Opcode::Return => vec![&mut self.arg1],
+            _ => vec![],
+        };
+        uses.into_iter().filter(|operand| !operand.is_empty()).collect()
+    }
+
+    /// The variable or temporary the quadruple assigns, if any.
+    pub fn def(&self) -> Option<&Operand> {
+        match self.op {
+            Opcode::Binary(_) | Opcode::Neg | Opcode::Not | Opcode::Assign | Opcode::Call | Opcode::Formal => Some(&self.result),
+            _ => None,
+        }
+    }
+
+    /// Target of a jump, `None` if this is not a jump or it has not been
+    /// backpatched yet.
+    pub fn jump_target(&self) -> Option<usize> {
+        match (self.op.is_jump(), &self.result) {
+            (true, Operand::Label(target)) => Some(*target),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Quadruple {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {}, {})", self.op, self.arg1, self.arg2, self.result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quadruple_display() {
+        let quads = [
+            Quadruple::new(Opcode::Func, Operand::Func("main".to_string()), Operand::Empty, Operand::Empty),
+            Quadruple::new(Opcode::Binary(BinOp::Rel(RelOp::Le)), Operand::Var("x.1".to_string()), Operand::Const(-3), Operand::Temp(2)),
+            Quadruple::new(Opcode::CondJump(RelOp::Ne), Operand::Temp(2), Operand::Const(0), Operand::Label(7)),
+            Quadruple::new(Opcode::Neg, Operand::Var("x".to_string()), Operand::Empty, Operand::Temp(3)),
+        ];
+        let text: Vec<_> = quads.iter().map(|q| q.to_string()).collect();
+        assert_eq!(text, vec![
+            "(func, main, , )",
+            "(<=, x.1, -3, t2)",
+            "(j!=, t2, 0, 7)",
+            "(uminus, x, , t3)",
+        ]);
+        assert_eq!(quads[2].jump_target(), Some(7));
+        assert_eq!(quads[1].jump_target(), None);
+    }
+
+    #[test]
+    fn test_binop_eval() {
+        assert_eq!(BinOp::from_symbol("%"), Some(BinOp::Mod));
+        assert_eq!(BinOp::from_symbol(">="), Some(BinOp::Rel(RelOp::Ge)));
+        assert_eq!(BinOp::from_symbol("&&"), None);
+        assert_eq!(BinOp::Add.eval(i32::MAX, 1), Some(i32::MIN));
+        assert_eq!(BinOp::Div.eval(i32::MIN, -1), Some(i32::MIN));
+        assert_eq!(BinOp::Mod.eval(1, 0), None);
+        assert_eq!(BinOp::Rel(RelOp::Lt).eval(1, 2), Some(1));
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+
+use super::{temp_index, Opcode, Operand, Quadruple};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Expected {
+        expected: String,
+        found: String,
+        line: usize,
+        column: usize,
+    },
+    UnknownOpcode {
+        op: String,
+        line: usize,
+        column: usize,
+    },
+    /// The number in front of a quadruple is not its position in the list.
+    WrongNumber {
+        expected: usize,
+        found: usize,
+        line: usize,
+        column: usize,
+    },
+}
+
+impl ParseError {
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            ParseError::Expected { line, column, .. }
+            | ParseError::UnknownOpcode { line, column, .. }
+            | ParseError::WrongNumber { line, column, .. } => (*line, *column),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Expected { expected, found, .. } => {
+                write!(f, "expected {}, found {}", expected, found)?;
+            }
+            ParseError::UnknownOpcode { op, .. } => {
+                write!(f, "unknown opcode `{}`", op)?;
+            }
+            ParseError::WrongNumber { expected, found, .. } => {
+                write!(f, "quadruple numbered {} should be {}", found, expected)?;
+            }
+        }
+        let (line, column) = self.position();
+        write!(f, " at line {}, column {}", line, column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Renders `quads` one per line as `N: (op, a, b, r)`, the format read back
+/// by `parse_quadruples`.
+pub fn format_quadruples(quads: &[Quadruple]) -> String {
+    quads
+        .iter()
+        .enumerate()
+        .map(|(i, quad)| format!("{}: {}\n", i + 1, quad))
+        .collect()
+}
+
+/// Parses the textual format produced by `format_quadruples`.
+pub fn parse_quadruples(text: &str) -> Result<Vec<Quadruple>, ParseError> {
+    let mut quads = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut cursor = Cursor { text: line, pos: 0, line: i + 1 };
+        cursor.skip_whitespace();
+        if cursor.at_end() {
+            continue;
+        }
+        quads.push(cursor.quadruple(quads.len() + 1)?);
+    }
+    Ok(quads)
+}
+
+/// What an operand position of an opcode accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// a variable, temporary or constant
+    Value,
+    /// like `Value`, or nothing
+    OptionalValue,
+    /// a variable or temporary that is assigned to
+    Dest,
+    Label,
+    Func,
+    /// a non-negative constant
+    Count,
+    Empty,
+}
+
+impl Kind {
+    fn description(self) -> &'static str {
+        match self {
+            Kind::Value => "a variable, temporary or constant",
+            Kind::OptionalValue => "a variable, temporary, constant or nothing",
+            Kind::Dest => "a variable or temporary",
+            Kind::Label => "a quadruple number",
+            Kind::Func => "a function name",
+            Kind::Count => "an argument count",
+            Kind::Empty => "nothing",
+        }
+    }
+}
+
+fn operand_kinds(op: Opcode) -> [Kind; 3] {
+    match op {
+        Opcode::Binary(_) => [Kind::Value, Kind::Value, Kind::Dest],
+        Opcode::Neg | Opcode::Not | Opcode::Assign => [Kind::Value, Kind::Empty, Kind::Dest],
+        Opcode::Jump => [Kind::Empty, Kind::Empty, Kind::Label],
+        Opcode::CondJump(_) => [Kind::Value, Kind::Value, Kind::Label],
+        Opcode::Param => [Kind::Value, Kind::Empty, Kind::Empty],
+        Opcode::Call => [Kind::Func, Kind::Count, Kind::Dest],
+        Opcode::Return => [Kind::OptionalValue, Kind::Empty, Kind::Empty],
+        Opcode::Func => [Kind::Func, Kind::Empty, Kind::Empty],
+        Opcode::Formal => [Kind::Empty, Kind::Empty, Kind::Dest],
+    }
+}
+
+/// `[A-Za-z_][A-Za-z0-9_]*`, optionally followed by `.<n>` as in the IR
+/// names of shadowing variables, and then by `#<n>` as in SSA versions.
+fn is_name(text: &str) -> bool {
+    let text = match text.split_once('#') {
+        Some((base, version)) if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) => base,
+        Some(_) => return false,
+        None => text,
+    };
+    let (base, suffix) = match text.split_once('.') {
+        Some((base, suffix)) => (base, Some(suffix)),
+        None => (text, None),
+    };
+    let mut chars = base.chars();
+    let head = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    head && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && suffix.is_none_or(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn parse_operand(text: &str, kind: Kind) -> Option<Operand> {
+    let dest = || match temp_index(text) {
+        Some(n) => Some(Operand::Temp(n)),
+        None => is_name(text).then(|| Operand::Var(text.to_string())),
+    };
+    let value = || match text.parse::<i32>() {
+        Ok(n) => Some(Operand::Const(n)),
+        Err(_) => dest(),
+    };
+    match kind {
+        _ if text.is_empty() => matches!(kind, Kind::Empty | Kind::OptionalValue).then_some(Operand::Empty),
+        Kind::Value | Kind::OptionalValue => value(),
+        Kind::Dest => dest(),
+        Kind::Label => text.parse().ok().filter(|_| text.starts_with(|c: char| c.is_ascii_digit())).map(Operand::Label),
+        Kind::Func => is_name(text).then(|| Operand::Func(text.to_string())),
+        Kind::Count => text.parse::<i32>().ok().filter(|n| *n >= 0).map(Operand::Const),
+        Kind::Empty => None,
+    }
+}
+
+struct Cursor<'a> {
+    text: &'a str,
+    /// byte offset into `text`
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn quadruple(&mut self, number: usize) -> Result<Quadruple, ParseError> {
+        // the `N:` prefix is optional, but must be right if present
+        if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            let column = self.column();
+            let digits = self.take_while(|c| c.is_ascii_digit());
+            let found = digits.parse().unwrap_or(usize::MAX);
+            if found != number {
+                return Err(ParseError::WrongNumber {
+                    expected: number,
+                    found,
+                    line: self.line,
+                    column,
+                });
+            }
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+        }
+        self.expect('(')?;
+
+        let mut fields = Vec::new();
+        for i in 0..4 {
+            self.skip_whitespace();
+            let column = self.column();
+            let field = self.take_while(|c| c != ',' && c != ')').trim_end();
+            fields.push((field, column));
+            self.expect(if i < 3 { ',' } else { ')' })?;
+        }
+        self.skip_whitespace();
+        if !self.at_end() {
+            return Err(self.expected("end of line"));
+        }
+
+        let (name, column) = fields[0];
+        let op = Opcode::from_name(name).ok_or_else(|| ParseError::UnknownOpcode {
+            op: name.to_string(),
+            line: self.line,
+            column,
+        })?;
+        let mut operands = Vec::new();
+        for (&(text, column), kind) in fields[1..].iter().zip(operand_kinds(op)) {
+            let operand = parse_operand(text, kind).ok_or_else(|| ParseError::Expected {
+                expected: kind.description().to_string(),
+                found: if text.is_empty() { "nothing".to_string() } else { format!("`{}`", text) },
+                line: self.line,
+                column,
+            })?;
+            operands.push(operand);
+        }
+        let [arg1, arg2, result] = <[Operand; 3]>::try_from(operands).unwrap();
+        Ok(Quadruple::new(op, arg1, arg2, result))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.text.len()
+    }
+
+    fn column(&self) -> usize {
+        self.text[..self.pos].chars().count() + 1
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek().filter(|&c| pred(c)) {
+            self.pos += c.len_utf8();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        }
+        else {
+            Err(self.expected(&format!("`{}`", expected)))
+        }
+    }
+
+    fn expected(&self, expected: &str) -> ParseError {
+        ParseError::Expected {
+            expected: expected.to_string(),
+            found: match self.peek() {
+                Some(c) => format!("`{}`", c),
+                None => "end of line".to_string(),
+            },
+            line: self.line,
+            column: self.column(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::ir::{BinOp, RelOp};
+    use crate::test_util::compile;
+
+    #[test]
+    fn test_round_trip() {
+        let quads = compile("int f(int a, int b) { return -a % b + !b; }
+            int main() {
+                int t1 = 2;
+                int x = 1;
+                while (x < 10 && !(x == 5) || t1 > 3) {
+                    int x = f(x, t1) / 2;
+                    t1 = x >= 0;
+                }
+                return x;
+            }");
+        let text = format_quadruples(&quads);
+        let parsed = parse_quadruples(&text).unwrap();
+        assert_eq!(parsed, quads);
+        assert_eq!(format_quadruples(&parsed), text);
+    }
+
+    #[test]
+    fn test_parse_hand_written() {
+        let quads = parse_quadruples("
+            // sum of 1..n
+            (func, sum, , )
+            (formal, , , n)
+            (=, 0, , s)
+            (j<=, n, 0, 9)     // loop exit
+            (+, s, n, t1)
+            (=, t1, , s)
+            (-, n, 1, n)
+            (j, , , 4)
+            (return, s, , )
+            10: (func, main, , )
+            11: (param, 10, , )
+            12: (call, sum, 1, t1)
+            13: (return, t1, , )
+        ").unwrap();
+        assert_eq!(quads[3], Quadruple::new(
+            Opcode::CondJump(RelOp::Le),
+            Operand::Var("n".to_string()),
+            Operand::Const(0),
+            Operand::Label(9),
+        ));
+        assert_eq!(quads[4].op, Opcode::Binary(BinOp::Add));
+        assert_eq!(Interpreter::new(&quads).run(), Ok(55));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| parse_quadruples(text).unwrap_err().to_string();
+        assert_eq!(error("(func, main, , )\n  (=, 1, x)"), "expected `,`, found `)` at line 2, column 11");
+        assert_eq!(error("(jump, , , 3)"), "unknown opcode `jump` at line 1, column 2");
+        assert_eq!(error("1: (func, main, , )\n3: (return, 0, , )"), "quadruple numbered 3 should be 2 at line 2, column 1");
+        assert_eq!(error("(j, , , x)"), "expected a quadruple number, found `x` at line 1, column 9");
+        assert_eq!(error("(+, a, , t1)"), "expected a variable, temporary or constant, found nothing at line 1, column 8");
+        assert_eq!(error("(=, 1, , 2)"), "expected a variable or temporary, found `2` at line 1, column 10");
+        assert_eq!(error("(=, 1, , x#)"), "expected a variable or temporary, found `x#` at line 1, column 10");
+        assert_eq!(error("(return, 0, , ) x"), "expected end of line, found `x` at line 1, column 17");
+        assert_eq!(error("(call, f, -1, t1)"), "expected an argument count, found `-1` at line 1, column 11");
+    }
+}
@@ -2,4 +2,16 @@ pub mod token;
 pub mod lexer;
 pub mod ast;
 pub mod parser;
+pub mod ir;
 pub mod codegen;
+pub mod cfg;
+pub mod dataflow;
+pub mod dominators;
+pub mod loops;
+pub mod ssa;
+pub mod opt;
+pub mod interpreter;
+pub mod evaluator;
+
+#[cfg(test)]
+pub(crate) mod test_util;
@@ -0,0 +1,178 @@
+use std::collections::BTreeSet;
+
+use crate::cfg::{BlockId, Cfg};
+use crate::dominators::Dominators;
+
+/// A natural loop: the blocks that can reach one of its back edges without
+/// passing through the header. Back edges into the same header make up one
+/// loop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub header: BlockId,
+    /// sources of the back edges
+    pub latches: Vec<BlockId>,
+    pub blocks: BTreeSet<BlockId>,
+    /// index of the innermost enclosing loop
+    pub parent: Option<usize>,
+    /// 1 for an outermost loop
+    pub depth: usize,
+}
+
+/// All natural loops of a `Cfg`, ordered by header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopNest {
+    pub loops: Vec<Loop>,
+}
+
+impl LoopNest {
+    /// Finds the back edges, those going to a block that dominates their
+    /// source, and the loop of every header.
+    pub fn build(cfg: &Cfg, dominators: &Dominators) -> LoopNest {
+        let reachable = |id: &BlockId| dominators.order.iter().any(|rpo| rpo.contains(id));
+        let mut loops: Vec<Loop> = Vec::new();
+        for header in 0..cfg.blocks.len() {
+            let latches: Vec<BlockId> = cfg.blocks[header]
+                .preds
+                .iter()
+                .copied()
+                .filter(|&pred| dominators.dominates(header, pred) && reachable(&pred))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+
+            let mut blocks = BTreeSet::from([header]);
+            let mut stack = latches.clone();
+            while let Some(id) = stack.pop() {
+                // unreachable code jumping into the loop is not part of it
+                if blocks.insert(id) {
+                    stack.extend(cfg.blocks[id].preds.iter().filter(|pred| reachable(pred)));
+                }
+            }
+            loops.push(Loop {
+                header,
+                latches,
+                blocks,
+                parent: None,
+                depth: 1,
+            });
+        }
+
+        // natural loops with different headers are nested or disjoint
+        for i in 0..loops.len() {
+            loops[i].parent = (0..loops.len())
+                .filter(|&j| j != i && loops[j].blocks.contains(&loops[i].header))
+                .min_by_key(|&j| loops[j].blocks.len());
+        }
+        for i in 0..loops.len() {
+            let mut parent = loops[i].parent;
+            while let Some(j) = parent {
+                loops[i].depth += 1;
+                parent = loops[j].parent;
+            }
+        }
+        LoopNest { loops }
+    }
+
+    /// Indices of the loops directly inside `parent`, or of the outermost
+    /// ones for `None`.
+    pub fn children(&self, parent: Option<usize>) -> impl Iterator<Item = usize> + '_ {
+        (0..self.loops.len()).filter(move |&i| self.loops[i].parent == parent)
+    }
+
+    fn fmt_loops(&self, f: &mut std::fmt::Formatter, parent: Option<usize>) -> std::fmt::Result {
+        let blocks = |ids: &mut dyn Iterator<Item = &BlockId>| {
+            let names: Vec<_> = ids.map(|id| format!("B{}", id)).collect();
+            format!("{{{}}}", names.join(", "))
+        };
+        for i in self.children(parent) {
+            let l = &self.loops[i];
+            writeln!(
+                f,
+                "{}loop B{}: blocks = {}, latches = {}",
+                "    ".repeat(l.depth - 1),
+                l.header,
+                blocks(&mut l.blocks.iter()),
+                blocks(&mut l.latches.iter()),
+            )?;
+            self.fmt_loops(f, Some(i))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for LoopNest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_loops(f, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{parse_quadruples, Quadruple};
+    use crate::test_util::compile;
+
+    fn loops(quads: &[Quadruple]) -> LoopNest {
+        let cfg = Cfg::build(quads);
+        LoopNest::build(&cfg, &Dominators::build(&cfg))
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        let quads = compile("int main() {
+            int i = 0;
+            int s = 0;
+            while (i < 3) {
+                int j = 0;
+                while (j < i) { s = s + j; j = j + 1; }
+                i = i + 1;
+            }
+            while (s > 0) { s = s - 2; }
+            return s;
+        }");
+        let nest = loops(&quads);
+        assert_eq!(nest.to_string(), "\
+loop B1: blocks = {B1, B3, B4, B5, B6, B7}, latches = {B7}
+    loop B4: blocks = {B4, B6}, latches = {B6}
+loop B8: blocks = {B8, B10}, latches = {B10}
+");
+        assert_eq!(nest.loops[1].parent, Some(0));
+        assert_eq!(nest.loops[1].depth, 2);
+    }
+
+    #[test]
+    fn test_no_loops_without_back_edges() {
+        // a jump backwards that does not close a cycle, and a cycle in
+        // unreachable code
+        let quads = parse_quadruples("
+            (func, main, , )
+            (j, , , 4)
+            (return, 1, , )
+            (j, , , 3)
+            (j, , , 6)
+            (j, , , 5)
+        ").unwrap();
+        assert_eq!(loops(&quads).loops, vec![]);
+    }
+
+    #[test]
+    fn test_unreachable_code_stays_out() {
+        // B3 is unreachable and jumps into the bodies of both loops
+        let quads = parse_quadruples("
+            (func, main, , )
+            (=, 0, , i)
+            (j>=, i, 3, 8)
+            (+, i, 1, i)
+            (j, , , 3)
+            (j<, i, 0, 4)
+            (+, i, 2, i)
+            (j<, i, 6, 7)
+            (return, i, , )
+        ").unwrap();
+        assert_eq!(loops(&quads).to_string(), "\
+loop B1: blocks = {B1, B2}, latches = {B2}
+loop B5: blocks = {B4, B5}, latches = {B4}
+");
+    }
+}
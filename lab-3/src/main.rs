@@ -3,15 +3,31 @@ use std::fs::File;
 use std::path::Path;
 use std::io::{ self, Read };
 
-use crate::lexer::Lexer;
-use crate::parser::Parser;
-use crate::codegen::CodeGenerator;
+use xjtu_codegen::token::Token;
+use xjtu_codegen::lexer::Lexer;
+use xjtu_codegen::parser::Parser;
+use xjtu_codegen::codegen::CodeGenerator;
+use xjtu_codegen::interpreter::Interpreter;
+use xjtu_codegen::ir::{self, Quadruple};
+use xjtu_codegen::cfg::Cfg;
+use xjtu_codegen::dataflow::{self, Chains, Liveness, ReachingDefinitions};
+use xjtu_codegen::ssa::Ssa;
+use xjtu_codegen::dominators::Dominators;
+use xjtu_codegen::loops::LoopNest;
+use xjtu_codegen::opt::cse::dump_dags;
+use xjtu_codegen::opt::pass::{pass_by_name, PassManager};
+use xjtu_codegen::opt::strength::induction_variables;
 
-mod token;
-mod lexer;
-mod ast;
-mod parser;
-mod codegen;
+/// Analyses to print for the final quadruples, as requested on the command
+/// line.
+#[derive(Default)]
+struct Reports {
+    dot: bool,
+    dags: bool,
+    dataflow: bool,
+    ssa: bool,
+    loops: bool,
+}
 
 fn main() -> io::Result<()> {
     let mut args = env::args();
@@ -20,7 +36,52 @@ fn main() -> io::Result<()> {
     args.next(); 
 
     // supports single file now
-    let file_path = match args.next() {
+    let mut file_path = None;
+    let mut reports = Reports::default();
+    let mut level = 0;
+    let mut print_after = Vec::new();
+    let mut print_before_all = false;
+    for arg in args {
+        match arg.as_str() {
+            "--dot" => reports.dot = true,
+            "--dags" => reports.dags = true,
+            "--dataflow" => reports.dataflow = true,
+            "--ssa" => reports.ssa = true,
+            "--loops" => reports.loops = true,
+            "--print-before-all" => print_before_all = true,
+            _ if arg.starts_with("-O") => match arg[2..].parse() {
+                Ok(n) if n <= 2 => level = n,
+                _ => {
+                    eprintln!("invalid optimisation level {}", arg);
+                    process::exit(1);
+                }
+            },
+            _ if arg.starts_with("--print-after=") => {
+                let name = &arg["--print-after=".len()..];
+                if pass_by_name(name).is_none() {
+                    eprintln!("unknown pass {}", name);
+                    process::exit(1);
+                }
+                print_after.push(name.to_string());
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option {}", arg);
+                process::exit(1);
+            }
+            _ => file_path = Some(arg),
+        }
+    }
+    // -O0 runs no passes to dump around
+    if level == 0 && (print_before_all || !print_after.is_empty()) {
+        eprintln!("--print-after and --print-before-all need -O1 or -O2");
+        process::exit(1);
+    }
+    let pipeline = PassManager::for_level(level);
+    if let Some(name) = print_after.iter().find(|&name| pipeline.passes.iter().all(|pass| pass.name() != name)) {
+        eprintln!("pass {} does not run at -O{}", name, level);
+        process::exit(1);
+    }
+    let file_path = match file_path {
         Some(f) => f,
         None => { 
             eprintln!("no input files"); 
@@ -37,43 +98,169 @@ fn main() -> io::Result<()> {
     let mut input = String::new();
     File::open(file_path)?.read_to_string(&mut input)?;
 
+    // quadruples in the textual IR format skip the frontend
+    if file_path.extension().is_some_and(|ext| ext == "quad") {
+        match ir::parse_quadruples(&input) {
+            Ok(mut quads) => {
+                print!("{}", ir::format_quadruples(&quads));
+                if level > 0 {
+                    optimise(&mut quads, level, print_after, print_before_all);
+                }
+                print_requested(&quads, &reports);
+                println!("\n=== Execution ===");
+                match Interpreter::new(&quads).run() {
+                    Ok(value) => println!("main returned {}", value),
+                    Err(e) => eprintln!("Runtime error: {}", e),
+                }
+            }
+            Err(e) => eprintln!("IR parse error: {}", e),
+        }
+        return Ok(());
+    }
+
     let lexer = Lexer::new(&input);
 
     match lexer.to_tokens() {
         Ok(tokens) => {
             println!("=== Tokens ===");
-            for (i, t) in tokens.iter().filter(|t| !matches!(t, token::Token::EOF)).enumerate() {
-                println!("({})\t{:?}", i + 1, t);
+            for (i, t) in tokens.iter().filter(|t| t.token != Token::EOF).enumerate() {
+                println!("({})\t{:?}", i + 1, t.token);
             }
             
             println!("\n=== Parsing ===");
             let mut parser = Parser::new(&tokens);
-            match parser.parse() {
-                Ok(func) => {
-                    println!("Parsed successfully!");
-                    println!("{:#?}", func);
-                    let mut codegen = CodeGenerator::new();
-                    match codegen.generate(&func) {
-                        Ok(()) => {
-                            codegen.print_quadruples();
-                            codegen.print_symbol_table();
-                        }
-                        Err(errors) => {
-                            for e in errors {
-                                eprintln!("{}", e);
-                            }
+            let (program, syntax_errors) = parser.parse_recovering();
+            for e in &syntax_errors {
+                eprintln!("Parse error: {}", e);
+            }
+            if syntax_errors.is_empty() {
+                println!("Parsed successfully!");
+            }
+            println!("{:#?}", program);
+
+            // semantic checks still run over the well-formed parts
+            let mut codegen = CodeGenerator::new();
+            match codegen.generate(&program) {
+                Ok(()) if syntax_errors.is_empty() => {
+                    codegen.print_quadruples();
+                    codegen.print_symbol_table();
+                    let mut quads = codegen.quadruples.clone();
+                    if level > 0 {
+                        optimise(&mut quads, level, print_after, print_before_all);
+                    }
+                    print_requested(&quads, &reports);
+
+                    if codegen.symbol_table.lookup("main").is_some() {
+                        println!("\n=== Execution ===");
+                        match Interpreter::new(&quads).run() {
+                            Ok(value) => println!("main returned {}", value),
+                            Err(e) => eprintln!("Runtime error: {}", e),
                         }
                     }
                 }
-                Err(err) => {
-                    eprintln!("Parse error: {}", err);
+                Ok(()) => {}
+                Err(errors) => {
+                    for e in errors {
+                        eprintln!("{}", e);
+                    }
                 }
             }
         }
         Err(invalid_tokens) => {
-            eprintln!("invalid tokens: {:?}", invalid_tokens);
+            for t in invalid_tokens {
+                let pos = t.span.start;
+                match t.token {
+                    Token::UnterminatedComment => {
+                        eprintln!("unterminated block comment opened at line {}, column {}", pos.line, pos.column);
+                    }
+                    _ => {
+                        eprintln!("invalid token {:?} at line {}, column {}", t.lexeme, pos.line, pos.column);
+                    }
+                }
+            }
         }
     }
 
-    return Ok(());
+    Ok(())
+}
+
+/// Runs the passes of `level`, printing the requested dumps, the optimised
+/// quadruples and what every pass did.
+fn optimise(quads: &mut Vec<Quadruple>, level: u32, print_after: Vec<String>, print_before_all: bool) {
+    let mut manager = PassManager::for_level(level);
+    manager.print_after = print_after;
+    manager.print_before_all = print_before_all;
+    let mut dump = String::new();
+    manager.run(quads, &mut dump).unwrap();
+    if !dump.is_empty() {
+        println!("\n=== Pass Dumps ===");
+        print!("{}", dump);
+    }
+    println!("\n=== Optimised ===");
+    print!("{}", ir::format_quadruples(quads));
+    println!("\n=== Pass Statistics ===");
+    print!("{}", manager.report());
+}
+
+fn print_requested(quads: &[Quadruple], reports: &Reports) {
+    if reports.dot {
+        print_cfg(quads);
+    }
+    if reports.dags {
+        print_block_dags(quads);
+    }
+    if reports.dataflow {
+        print_dataflow_facts(quads);
+    }
+    if reports.ssa {
+        print_ssa_form(quads);
+    }
+    if reports.loops {
+        print_loop_nest(quads);
+    }
+}
+
+fn print_cfg(quads: &[Quadruple]) {
+    println!("\n=== CFG ===");
+    print!("{}", Cfg::build(quads).to_dot(quads));
+}
+
+fn print_block_dags(quads: &[Quadruple]) {
+    println!("\n=== DAGs ===");
+    print!("{}", dump_dags(quads));
+}
+
+fn print_dataflow_facts(quads: &[Quadruple]) {
+    let cfg = Cfg::build(quads);
+    println!("\n=== Live Variables ===");
+    print!("{}", dataflow::solve(&Liveness, &cfg, quads).format_with(dataflow::format_set));
+    println!("\n=== Reaching Definitions ===");
+    print!("{}", dataflow::solve(&ReachingDefinitions::new(quads), &cfg, quads).format_with(dataflow::format_set));
+    println!("\n=== Use-Def Chains ===");
+    print!("{}", Chains::build(&cfg, quads));
+}
+
+fn print_ssa_form(quads: &[Quadruple]) {
+    let ssa = Ssa::build(quads);
+    println!("\n=== Dominators ===");
+    print!("{}", ssa.dominators);
+    println!("\n=== SSA ===");
+    print!("{}", ssa);
+    println!("\n=== Out of SSA ===");
+    print!("{}", ir::format_quadruples(&ssa.to_quadruples()));
+}
+
+fn print_loop_nest(quads: &[Quadruple]) {
+    let cfg = Cfg::build(quads);
+    let nest = LoopNest::build(&cfg, &Dominators::build(&cfg));
+    println!("\n=== Loops ===");
+    print!("{}", nest);
+
+    let chains = Chains::build(&cfg, quads);
+    for l in &nest.loops {
+        println!("induction variables of loop B{}:", l.header);
+        for iv in induction_variables(quads, &cfg, &chains, l) {
+            println!("    {}", iv);
+        }
+    }
 }
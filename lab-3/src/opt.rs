@@ -0,0 +1,83 @@
+use crate::ir::{BinOp, Opcode, Operand, Quadruple};
+
+pub mod constant;
+pub mod copy;
+pub mod cse;
+pub mod dce;
+pub mod licm;
+pub mod pass;
+pub mod strength;
+pub mod temps;
+
+pub use constant::propagate_constants;
+pub use copy::propagate_copies;
+pub use cse::eliminate_common_subexpressions;
+pub use dce::eliminate_dead_code;
+pub use licm::hoist_loop_invariants;
+pub use pass::{Pass, PassManager};
+pub use strength::reduce_strength;
+pub use temps::recycle_temps;
+
+/// Removes the quadruples marked in `removed` and renumbers every jump
+/// target. A jump to a removed quadruple goes to the next one that is kept.
+pub fn remove_quadruples(quads: &mut Vec<Quadruple>, removed: &[bool]) {
+    // new number of every old quadruple, and of the position past the end
+    let mut number = Vec::with_capacity(quads.len() + 1);
+    let mut next = 1;
+    for &is_removed in removed {
+        number.push(next);
+        if !is_removed {
+            next += 1;
+        }
+    }
+    number.push(next);
+
+    for quad in quads.iter_mut() {
+        if quad.op.is_jump() && let Operand::Label(target) = &mut quad.result && (1..=number.len()).contains(target) {
+            *target = number[*target - 1];
+        }
+    }
+
+    let mut index = 0;
+    quads.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+}
+
+/// Whether `quad` only computes its result, so that it can be removed or
+/// moved without changing what the program does other than through that
+/// result. A division is not, unless its divisor is a non-zero constant,
+/// since it may fail at run time.
+pub fn is_pure(quad: &Quadruple) -> bool {
+    match quad.op {
+        Opcode::Binary(BinOp::Div | BinOp::Mod) => matches!(quad.arg2, Operand::Const(n) if n != 0),
+        Opcode::Binary(_) | Opcode::Neg | Opcode::Not | Opcode::Assign => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{format_quadruples, parse_quadruples};
+
+    #[test]
+    fn test_remove_quadruples() {
+        let mut quads = parse_quadruples("
+            (func, main, , )
+            (j, , , 4)
+            (=, 1, , x)
+            (=, 2, , x)
+            (j<, x, 3, 2)
+            (j, , , 7)
+        ").unwrap();
+        remove_quadruples(&mut quads, &[false, false, true, true, false, false]);
+        assert_eq!(format_quadruples(&quads), "\
+1: (func, main, , )
+2: (j, , , 3)
+3: (j<, x, 3, 2)
+4: (j, , , 5)
+");
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::remove_quadruples;
+use crate::cfg::Cfg;
+use crate::ir::{Opcode, Operand, Quadruple};
+
+/// Variables and temporaries known to hold a constant.
+type Constants = HashMap<Operand, i32>;
+
+/// Folds operations on constants and propagates constants through
+/// assignments, within and across basic blocks. Arithmetic wraps like i32;
+/// a division by a constant zero is left for the interpreter to report.
+/// Conditional jumps with a constant outcome become `j` or are removed.
+///
+/// Runs until nothing changes and returns the number of rewritten or
+/// removed quadruples.
+pub fn propagate_constants(quads: &mut Vec<Quadruple>) -> usize {
+    let mut changes = 0;
+    loop {
+        let round = propagate_once(quads);
+        if round == 0 {
+            return changes;
+        }
+        changes += round;
+    }
+}
+
+fn propagate_once(quads: &mut Vec<Quadruple>) -> usize {
+    let cfg = Cfg::build(quads);
+    let entry_constants = constants_at_entry(&cfg, quads);
+    let mut changes = 0;
+    let mut removed = vec![false; quads.len()];
+
+    for block in &cfg.blocks {
+        // nothing is known about unreachable blocks
+        let Some(mut constants) = entry_constants[block.id].clone() else {
+            continue;
+        };
+        for index in block.range() {
+            let quad = &mut quads[index];
+            let mut changed = false;
+            for operand in quad.uses_mut() {
+                if let Some(&value) = constants.get(operand) {
+                    *operand = Operand::Const(value);
+                    changed = true;
+                }
+            }
+            match fold(quad) {
+                Some(Some(folded)) => {
+                    *quad = folded;
+                    changed = true;
+                }
+                Some(None) => {
+                    removed[index] = true;
+                    changed = true;
+                }
+                None => {}
+            }
+            if changed {
+                changes += 1;
+            }
+            transfer(&mut constants, quad);
+        }
+    }
+
+    if removed.contains(&true) {
+        remove_quadruples(quads, &removed);
+    }
+    changes
+}
+
+/// Solves the constants holding on entry to every block, `None` for blocks
+/// that cannot be reached. A value is constant at a join only if it is the
+/// same constant on every incoming path that has been reached so far.
+fn constants_at_entry(cfg: &Cfg, quads: &[Quadruple]) -> Vec<Option<Constants>> {
+    let mut entry: Vec<Option<Constants>> = vec![None; cfg.blocks.len()];
+    let mut exit: Vec<Option<Constants>> = vec![None; cfg.blocks.len()];
+    let is_entry: Vec<bool> = (0..cfg.blocks.len()).map(|id| cfg.functions.iter().any(|f| f.start == id)).collect();
+
+    let mut worklist: VecDeque<usize> = (0..cfg.blocks.len()).collect();
+    while let Some(id) = worklist.pop_front() {
+        let block = &cfg.blocks[id];
+        let constants = if is_entry[id] {
+            Some(Constants::new())
+        }
+        else {
+            meet(block.preds.iter().filter_map(|&pred| exit[pred].as_ref()))
+        };
+        let out = constants.clone().map(|mut constants| {
+            for quad in &quads[block.range()] {
+                transfer(&mut constants, quad);
+            }
+            constants
+        });
+        entry[id] = constants;
+        if out != exit[id] {
+            exit[id] = out;
+            for &succ in &block.succs {
+                if !worklist.contains(&succ) {
+                    worklist.push_back(succ);
+                }
+            }
+        }
+    }
+    entry
+}
+
+fn meet<'a>(mut incoming: impl Iterator<Item = &'a Constants>) -> Option<Constants> {
+    let mut constants = incoming.next()?.clone();
+    for other in incoming {
+        constants.retain(|operand, value| other.get(operand) == Some(value));
+    }
+    Some(constants)
+}
+
+fn value(constants: &Constants, operand: &Operand) -> Option<i32> {
+    match operand {
+        Operand::Const(n) => Some(*n),
+        _ => constants.get(operand).copied(),
+    }
+}
+
+/// Records the effect of `quad` on the known constants.
+fn transfer(constants: &mut Constants, quad: &Quadruple) {
+    let Some(def) = quad.def() else {
+        return;
+    };
+    let result = match quad.op {
+        Opcode::Assign => value(constants, &quad.arg1),
+        Opcode::Neg => value(constants, &quad.arg1).map(i32::wrapping_neg),
+        Opcode::Not => value(constants, &quad.arg1).map(|v| (v == 0) as i32),
+        Opcode::Binary(op) => match (value(constants, &quad.arg1), value(constants, &quad.arg2)) {
+            (Some(a), Some(b)) => op.eval(a, b),
+            _ => None,
+        },
+        _ => None,
+    };
+    match result {
+        Some(v) => constants.insert(def.clone(), v),
+        None => constants.remove(def),
+    };
+}
+
+/// The simplified form of `quad` if all of its operands are constant:
+/// `Some(None)` means the quadruple can be dropped.
+fn fold(quad: &Quadruple) -> Option<Option<Quadruple>> {
+    let assign = |v: i32| Some(Some(Quadruple::new(Opcode::Assign, Operand::Const(v), Operand::Empty, quad.result.clone())));
+    match (quad.op, &quad.arg1, &quad.arg2) {
+        (Opcode::Binary(op), &Operand::Const(a), &Operand::Const(b)) => op.eval(a, b).and_then(assign),
+        (Opcode::Neg, &Operand::Const(a), _) => assign(a.wrapping_neg()),
+        (Opcode::Not, &Operand::Const(a), _) => assign((a == 0) as i32),
+        (Opcode::CondJump(op), &Operand::Const(a), &Operand::Const(b)) => {
+            if op.holds(a, b) {
+                Some(Some(Quadruple::new(Opcode::Jump, Operand::Empty, Operand::Empty, quad.result.clone())))
+            }
+            else {
+                Some(None)
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{format_quadruples, parse_quadruples};
+    use crate::test_util::{compile, optimise};
+
+    #[test]
+    fn test_fold_in_block() {
+        assert_eq!(optimise(compile("int main() { int y = 4; int x = 2 * 3 + y; return -x; }"), propagate_constants).1, "\
+1: (func, main, , )
+2: (=, 4, , y)
+3: (=, 6, , t1)
+4: (=, 10, , t2)
+5: (=, 10, , x)
+6: (=, -10, , t3)
+7: (return, -10, , )
+");
+    }
+
+    #[test]
+    fn test_wrapping_and_division_by_zero() {
+        assert_eq!(optimise(compile("int main() { int x = 2147483647; int z = 0; return (x + 1) / z; }"), propagate_constants).1, "\
+1: (func, main, , )
+2: (=, 2147483647, , x)
+3: (=, 0, , z)
+4: (=, -2147483648, , t1)
+5: (/, -2147483648, 0, t2)
+6: (return, t2, , )
+");
+    }
+
+    #[test]
+    fn test_constant_branches() {
+        // the else branch becomes unreachable, so `x` is 6 after the if
+        assert_eq!(optimise(compile("int main() {
+            int x = 5;
+            if (x > 3) { x = x + 1; } else { x = 0; }
+            if (x == 0) { x = 7; }
+            return x;
+        }"), propagate_constants).1, "\
+1: (func, main, , )
+2: (=, 5, , x)
+3: (j, , , 5)
+4: (j, , , 8)
+5: (=, 6, , t1)
+6: (=, 6, , x)
+7: (j, , , 9)
+8: (=, 0, , x)
+9: (j, , , 11)
+10: (=, 7, , x)
+11: (return, 6, , )
+");
+    }
+
+    #[test]
+    fn test_loops() {
+        // `k` is the same on both paths into the loop header, `i` is not
+        assert_eq!(optimise(compile("int main() {
+            int i = 0;
+            int k = 3;
+            while (i < 10) { i = i + k; }
+            return i * k;
+        }"), propagate_constants).1, "\
+1: (func, main, , )
+2: (=, 0, , i)
+3: (=, 3, , k)
+4: (j<, i, 10, 6)
+5: (j, , , 9)
+6: (+, i, 3, t1)
+7: (=, t1, , i)
+8: (j, , , 4)
+9: (*, i, 3, t2)
+10: (return, t2, , )
+");
+    }
+
+    #[test]
+    fn test_functions_are_separate() {
+        let mut quads = parse_quadruples("
+            (func, f, , )
+            (formal, , , x)
+            (return, x, , )
+            (func, main, , )
+            (=, 1, , x)
+            (param, x, , )
+            (call, f, 1, t1)
+            (+, t1, x, t2)
+            (return, t2, , )
+        ").unwrap();
+        assert_eq!(propagate_constants(&mut quads), 2);
+        assert_eq!(format_quadruples(&quads), "\
+1: (func, f, , )
+2: (formal, , , x)
+3: (return, x, , )
+4: (func, main, , )
+5: (=, 1, , x)
+6: (param, 1, , )
+7: (call, f, 1, t1)
+8: (+, t1, 1, t2)
+9: (return, t2, , )
+");
+    }
+}
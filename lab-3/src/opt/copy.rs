@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+
+use super::remove_quadruples;
+use crate::cfg::{BasicBlock, Cfg};
+use crate::dataflow::{self, Analysis, Direction};
+use crate::ir::{Opcode, Operand, Quadruple};
+
+/// A copy `dest = source` between variables or temporaries, as
+/// `(dest, source)`.
+type CopyPair = (Operand, Operand);
+
+fn as_copy(quad: &Quadruple) -> Option<CopyPair> {
+    match (&quad.op, &quad.arg1) {
+        (Opcode::Assign, source @ (Operand::Var(_) | Operand::Temp(_))) => Some((quad.result.clone(), source.clone())),
+        _ => None,
+    }
+}
+
+/// Available copies: those executed on every path to a point, with neither
+/// side assigned since.
+struct AvailableCopies {
+    /// every copy in the program, the top of the lattice
+    all: BTreeSet<CopyPair>,
+}
+
+impl AvailableCopies {
+    fn step(available: &mut BTreeSet<CopyPair>, quad: &Quadruple) {
+        if let Some(def) = quad.def() {
+            available.retain(|(dest, source)| dest != def && source != def);
+        }
+        if let Some(copy) = as_copy(quad) && copy.0 != copy.1 {
+            available.insert(copy);
+        }
+    }
+}
+
+impl Analysis for AvailableCopies {
+    type Fact = BTreeSet<CopyPair>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        self.all.clone()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.retain(|copy| other.contains(copy));
+    }
+
+    fn transfer(&self, block: &BasicBlock, quads: &[Quadruple], fact: &Self::Fact) -> Self::Fact {
+        let mut available = fact.clone();
+        for quad in &quads[block.range()] {
+            Self::step(&mut available, quad);
+        }
+        available
+    }
+}
+
+/// Replaces every read of a variable or temporary with the one it was
+/// copied from, wherever that copy is available, and removes copies of a
+/// name to itself. The copies themselves are left for dead code
+/// elimination.
+///
+/// Runs until nothing changes and returns the number of rewritten or
+/// removed quadruples.
+pub fn propagate_copies(quads: &mut Vec<Quadruple>) -> usize {
+    let mut changes = 0;
+    loop {
+        let round = propagate_once(quads);
+        if round == 0 {
+            return changes;
+        }
+        changes += round;
+    }
+}
+
+fn propagate_once(quads: &mut Vec<Quadruple>) -> usize {
+    let cfg = Cfg::build(quads);
+    let analysis = AvailableCopies {
+        all: quads.iter().filter_map(as_copy).filter(|(dest, source)| dest != source).collect(),
+    };
+    let entry = dataflow::solve(&analysis, &cfg, quads).ins;
+    let reachable = cfg.reachable();
+    let mut changes = 0;
+    let mut removed = vec![false; quads.len()];
+
+    for block in cfg.blocks.iter().filter(|b| reachable[b.id]) {
+        let mut available = entry[block.id].clone();
+        for index in block.range() {
+            let quad = &mut quads[index];
+            if as_copy(quad).is_some_and(|(dest, source)| dest == source) {
+                removed[index] = true;
+                changes += 1;
+                continue;
+            }
+            let mut changed = false;
+            for operand in quad.uses_mut() {
+                // copies to the same name kill each other, so at most one
+                // is available
+                if let Some((_, source)) = available.iter().find(|(dest, _)| dest == operand) {
+                    *operand = source.clone();
+                    changed = true;
+                }
+            }
+            if changed {
+                changes += 1;
+            }
+            AvailableCopies::step(&mut available, quad);
+        }
+    }
+
+    if removed.contains(&true) {
+        remove_quadruples(quads, &removed);
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse_quadruples;
+    use crate::test_util::{compile, optimise};
+
+    #[test]
+    fn test_declarations() {
+        assert_eq!(optimise(compile("int main() { int a = 2; int b = a + 1; int c = b * b; return c; }"), propagate_copies).1, "\
+1: (func, main, , )
+2: (=, 2, , a)
+3: (+, a, 1, t1)
+4: (=, t1, , b)
+5: (*, t1, t1, t2)
+6: (=, t2, , c)
+7: (return, t2, , )
+");
+    }
+
+    #[test]
+    fn test_copies_across_blocks() {
+        // `y = x` is available after the if only while neither is assigned
+        assert_eq!(optimise(parse_quadruples("
+            (func, main, , )
+            (=, 1, , x)
+            (=, x, , y)
+            (j>, y, 0, 6)
+            (=, y, , z)
+            (+, y, 1, t1)
+            (=, 5, , x)
+            (+, y, x, t2)
+            (return, t2, , )
+        ").unwrap(), propagate_copies).1, "\
+1: (func, main, , )
+2: (=, 1, , x)
+3: (=, x, , y)
+4: (j>, x, 0, 6)
+5: (=, x, , z)
+6: (+, x, 1, t1)
+7: (=, 5, , x)
+8: (+, y, x, t2)
+9: (return, t2, , )
+");
+    }
+
+    #[test]
+    fn test_loops_and_self_copies() {
+        // `s = t1` reaches the header only along the back edge, and copying
+        // `b` to itself goes
+        assert_eq!(optimise(parse_quadruples("
+            (func, main, , )
+            (=, 0, , s)
+            (=, s, , a)
+            (=, a, , b)
+            (=, b, , b)
+            (j>, s, 3, 10)
+            (+, s, 1, t1)
+            (=, t1, , s)
+            (j, , , 6)
+            (return, s, , )
+        ").unwrap(), propagate_copies).1, "\
+1: (func, main, , )
+2: (=, 0, , s)
+3: (=, s, , a)
+4: (=, s, , b)
+5: (j>, s, 3, 9)
+6: (+, s, 1, t1)
+7: (=, t1, , s)
+8: (j, , , 5)
+9: (return, s, , )
+");
+    }
+}
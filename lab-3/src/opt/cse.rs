@@ -0,0 +1,336 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use super::remove_quadruples;
+use crate::cfg::Cfg;
+use crate::ir::{BinOp, Opcode, Operand, Quadruple, RelOp};
+
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    /// the value a variable or temporary has on entry to the block
+    Leaf(Operand),
+    Const(i32),
+    /// a pure operation on other nodes
+    Op(Opcode, Vec<NodeId>),
+    /// the result of a `call` or `formal`, never shared
+    Opaque(Opcode),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DagNode {
+    pub kind: NodeKind,
+    /// variables and temporaries currently holding the value, oldest first
+    pub labels: Vec<Operand>,
+}
+
+/// The DAG of one basic block, built by value numbering: every value is a
+/// node, and an operation on the same nodes as an earlier one is the same
+/// node.
+///
+/// Quadruples are added in order, and each is turned into the quadruples
+/// to emit instead. A computation already available in some variable or
+/// temporary becomes a copy from it, and every operand is read from the
+/// oldest holder of its value, which leaves later copies unused.
+#[derive(Debug, Default)]
+pub struct Dag {
+    pub nodes: Vec<DagNode>,
+    ops: HashMap<(Opcode, Vec<NodeId>), NodeId>,
+    value_of: HashMap<Operand, NodeId>,
+}
+
+impl Dag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `quad` to the DAG. Returns the quadruples to emit for it, and
+    /// whether it was replaced by a copy of an existing value.
+    pub fn add(&mut self, quad: &Quadruple) -> (Vec<Quadruple>, bool) {
+        let mut rewritten = quad.clone();
+        let mut operands = Vec::new();
+        for operand in rewritten.uses_mut() {
+            let node = self.node_of(operand);
+            *operand = self.operand_for(node);
+            operands.push(node);
+        }
+
+        let Some(def) = quad.def().cloned() else {
+            return (vec![rewritten], false);
+        };
+        let (node, is_new) = match quad.op {
+            Opcode::Assign => (operands[0], false),
+            Opcode::Binary(_) | Opcode::Neg | Opcode::Not => {
+                if let Opcode::Binary(op) = quad.op && is_commutative(op) {
+                    operands.sort_unstable();
+                }
+                let key = (quad.op, operands);
+                match self.ops.get(&key) {
+                    Some(&node) if !self.nodes[node].labels.is_empty() => (node, false),
+                    _ => {
+                        let node = self.push(NodeKind::Op(key.0, key.1.clone()));
+                        self.ops.insert(key, node);
+                        (node, true)
+                    }
+                }
+            }
+            _ => (self.push(NodeKind::Opaque(quad.op)), true),
+        };
+
+        if self.value_of.get(&def) == Some(&node) {
+            // `x = x`
+            return (vec![], quad.op != Opcode::Assign);
+        }
+        let emitted = if is_new {
+            rewritten
+        }
+        else {
+            Quadruple::new(Opcode::Assign, self.operand_for(node), Operand::Empty, def.clone())
+        };
+        self.assign(def, node);
+        (vec![emitted], quad.op != Opcode::Assign && !is_new)
+    }
+
+    fn push(&mut self, kind: NodeKind) -> NodeId {
+        self.nodes.push(DagNode { kind, labels: Vec::new() });
+        self.nodes.len() - 1
+    }
+
+    fn node_of(&mut self, operand: &Operand) -> NodeId {
+        if let Some(&node) = self.value_of.get(operand) {
+            return node;
+        }
+        let kind = match operand {
+            Operand::Const(n) => NodeKind::Const(*n),
+            _ => NodeKind::Leaf(operand.clone()),
+        };
+        if let Some(node) = self.nodes.iter().position(|n| n.kind == kind) {
+            return node;
+        }
+        let node = self.push(kind);
+        if !matches!(operand, Operand::Const(_)) {
+            self.assign(operand.clone(), node);
+        }
+        node
+    }
+
+    /// The operand to read `node` from.
+    fn operand_for(&self, node: NodeId) -> Operand {
+        match &self.nodes[node].kind {
+            NodeKind::Const(n) => Operand::Const(*n),
+            _ => self.nodes[node].labels[0].clone(),
+        }
+    }
+
+    fn assign(&mut self, target: Operand, node: NodeId) {
+        if let Some(old) = self.value_of.insert(target.clone(), node) {
+            self.nodes[old].labels.retain(|label| *label != target);
+        }
+        self.nodes[node].labels.push(target);
+    }
+}
+
+impl std::fmt::Display for Dag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (id, node) in self.nodes.iter().enumerate() {
+            write!(f, "n{} = ", id)?;
+            match &node.kind {
+                NodeKind::Leaf(operand) => write!(f, "{}0", operand)?,
+                NodeKind::Const(n) => write!(f, "{}", n)?,
+                NodeKind::Op(op, children) => {
+                    write!(f, "{}", op)?;
+                    for child in children {
+                        write!(f, " n{}", child)?;
+                    }
+                }
+                NodeKind::Opaque(op) => write!(f, "{}", op)?,
+            }
+            let labels: Vec<_> = node.labels.iter().map(|l| l.to_string()).collect();
+            writeln!(f, " [{}]", labels.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_commutative(op: BinOp) -> bool {
+    matches!(op, BinOp::Add | BinOp::Mul | BinOp::Rel(RelOp::Eq) | BinOp::Rel(RelOp::Ne))
+}
+
+/// Removes repeated computations within each basic block. Copies the
+/// elimination leaves into temporaries that end up unused are dropped.
+/// Returns the number of computations removed.
+pub fn eliminate_common_subexpressions(quads: &mut Vec<Quadruple>) -> usize {
+    let cfg = Cfg::build(quads);
+    let mut output = Vec::with_capacity(quads.len());
+    // new index of the first quadruple of every block
+    let mut block_start = Vec::with_capacity(cfg.blocks.len());
+    let mut copies = Vec::new();
+    let mut removed = 0;
+
+    for block in &cfg.blocks {
+        block_start.push(output.len());
+        let mut dag = Dag::new();
+        for quad in &quads[block.range()] {
+            let (emitted, is_copy) = dag.add(quad);
+            if is_copy {
+                removed += 1;
+                copies.extend(output.len()..output.len() + emitted.len());
+            }
+            output.extend(emitted);
+        }
+    }
+
+    // jump targets are always the first quadruple of a block
+    let end = output.len();
+    for quad in &mut output {
+        if quad.op.is_jump() && let Operand::Label(target) = &mut quad.result {
+            if (1..=quads.len()).contains(target) {
+                *target = block_start[cfg.block_of[*target - 1]] + 1;
+            }
+            else if *target == quads.len() + 1 {
+                *target = end + 1;
+            }
+        }
+    }
+
+    let used: HashSet<Operand> = output.iter().flat_map(|q| q.uses()).cloned().collect();
+    let mut dead = vec![false; output.len()];
+    for index in copies {
+        if let Operand::Temp(_) = output[index].result && !used.contains(&output[index].result) {
+            dead[index] = true;
+        }
+    }
+    remove_quadruples(&mut output, &dead);
+
+    *quads = output;
+    removed
+}
+
+/// Renders the DAG of every basic block.
+pub fn dump_dags(quads: &[Quadruple]) -> String {
+    let cfg = Cfg::build(quads);
+    let mut dump = String::new();
+    for block in &cfg.blocks {
+        let mut dag = Dag::new();
+        for quad in &quads[block.range()] {
+            dag.add(quad);
+        }
+        writeln!(dump, "B{}:", block.id).unwrap();
+        for line in dag.to_string().lines() {
+            writeln!(dump, "    {}", line).unwrap();
+        }
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::ir::{format_quadruples, parse_quadruples};
+    use crate::test_util::{compile, optimise};
+
+    #[test]
+    fn test_repeated_expression() {
+        let quads = compile("int main() { int a = 3; int b = 4; int x = a * b + b * a; return x; }");
+        assert_eq!(optimise(quads, eliminate_common_subexpressions).1, "\
+1: (func, main, , )
+2: (=, 3, , a)
+3: (=, 4, , b)
+4: (*, 3, 4, t1)
+5: (+, t1, t1, t3)
+6: (=, t3, , x)
+7: (return, t3, , )
+");
+    }
+
+    #[test]
+    fn test_reassigned_operand() {
+        // `a + b` is recomputed once `a` changes, and `t` keeps the old value
+        let quads = parse_quadruples("
+            (func, main, , )
+            (=, 1, , a)
+            (=, 2, , b)
+            (+, a, b, t1)
+            (=, a, , t)
+            (=, 5, , a)
+            (+, a, b, t2)
+            (+, t, b, t3)
+            (*, t2, t3, t4)
+            (return, t4, , )
+        ").unwrap();
+        assert_eq!(optimise(quads, eliminate_common_subexpressions).1, "\
+1: (func, main, , )
+2: (=, 1, , a)
+3: (=, 2, , b)
+4: (+, 1, 2, t1)
+5: (=, 1, , t)
+6: (=, 5, , a)
+7: (+, 5, 2, t2)
+8: (*, t2, t1, t4)
+9: (return, t4, , )
+");
+    }
+
+    #[test]
+    fn test_blocks_and_jumps() {
+        // nothing is shared between blocks, and jump targets follow the
+        // shorter blocks
+        let quads = compile("int main() {
+            int a = 2;
+            int i = 0;
+            while (i < a * a) { i = i + a * a; }
+            return i;
+        }");
+        assert_eq!(optimise(quads, eliminate_common_subexpressions).1, "\
+1: (func, main, , )
+2: (=, 2, , a)
+3: (=, 0, , i)
+4: (*, a, a, t1)
+5: (j<, i, t1, 7)
+6: (j, , , 11)
+7: (*, a, a, t2)
+8: (+, i, t2, t3)
+9: (=, t3, , i)
+10: (j, , , 4)
+11: (return, i, , )
+");
+    }
+
+    #[test]
+    fn test_live_out_copies_are_kept() {
+        let quads = parse_quadruples("
+            (func, main, , )
+            (formal, , , a)
+            (*, a, 2, t1)
+            (*, a, 2, t2)
+            (j, , , 6)
+            (return, t2, , )
+        ").unwrap();
+        let mut optimised = quads.clone();
+        assert_eq!(eliminate_common_subexpressions(&mut optimised), 1);
+        assert_eq!(Interpreter::new(&optimised).call("main", &[7]), Ok(14));
+        assert_eq!(format_quadruples(&optimised), "\
+1: (func, main, , )
+2: (formal, , , a)
+3: (*, a, 2, t1)
+4: (=, t1, , t2)
+5: (j, , , 6)
+6: (return, t2, , )
+");
+    }
+
+    #[test]
+    fn test_dump_dags() {
+        let quads = compile("int main() { int a = 3; int b = a * 2; int c = -(2 * a) + b; return c; }");
+        assert_eq!(dump_dags(&quads), "\
+B0:
+    n0 = 3 [a]
+    n1 = 2 []
+    n2 = * n0 n1 [t1, b, t2]
+    n3 = uminus n2 [t3]
+    n4 = + n2 n3 [t4, c]
+");
+    }
+}
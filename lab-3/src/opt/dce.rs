@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+
+use super::{is_pure, remove_quadruples};
+use crate::cfg::Cfg;
+use crate::dataflow::{self, Liveness};
+use crate::ir::{Opcode, Operand, Quadruple};
+
+/// Removes unreachable blocks, assignments whose value is never read, and
+/// jumps to the next quadruple, and retargets jumps to unconditional jumps
+/// at their final destination. Jump targets are renumbered as quadruples
+/// go away.
+///
+/// Runs until nothing changes and returns the number of removed or
+/// retargeted quadruples.
+pub fn eliminate_dead_code(quads: &mut Vec<Quadruple>) -> usize {
+    let mut changes = 0;
+    loop {
+        let mut round = thread_jumps(quads);
+        let mut removed = vec![false; quads.len()];
+        mark_jumps_to_next(quads, &mut removed);
+        mark_unreachable(quads, &mut removed);
+        mark_dead_assignments(quads, &mut removed);
+        round += removed.iter().filter(|&&r| r).count();
+        if round == 0 {
+            return changes;
+        }
+        remove_quadruples(quads, &removed);
+        changes += round;
+    }
+}
+
+/// Makes every jump to an unconditional jump go to where that one leads.
+fn thread_jumps(quads: &mut [Quadruple]) -> usize {
+    let mut changes = 0;
+    for i in 0..quads.len() {
+        let Some(target) = quads[i].jump_target() else {
+            continue;
+        };
+        let mut last = target;
+        let mut seen = HashSet::from([target]);
+        while let Some(next) = quads.get(last.wrapping_sub(1)).filter(|q| q.op == Opcode::Jump).and_then(Quadruple::jump_target) {
+            last = next;
+            if !seen.insert(next) {
+                // an endless loop of jumps, left alone
+                last = target;
+                break;
+            }
+        }
+        if last != target {
+            quads[i].result = Operand::Label(last);
+            changes += 1;
+        }
+    }
+    changes
+}
+
+fn mark_jumps_to_next(quads: &[Quadruple], removed: &mut [bool]) {
+    for (i, quad) in quads.iter().enumerate() {
+        // a condition has no side effects, so either way control goes on
+        if quad.jump_target() == Some(i + 2) {
+            removed[i] = true;
+        }
+    }
+}
+
+fn mark_unreachable(quads: &[Quadruple], removed: &mut [bool]) {
+    let cfg = Cfg::build(quads);
+    let reached = cfg.reachable();
+    for block in cfg.blocks.iter().filter(|b| !reached[b.id]) {
+        removed[block.range()].fill(true);
+    }
+}
+
+fn mark_dead_assignments(quads: &[Quadruple], removed: &mut [bool]) {
+    let cfg = Cfg::build(quads);
+    let live_out = dataflow::solve(&Liveness, &cfg, quads).outs;
+    for block in &cfg.blocks {
+        let mut live = live_out[block.id].clone();
+        for index in block.range().rev() {
+            let quad = &quads[index];
+            if let Some(def) = quad.def() && is_pure(quad) && !live.contains(def) {
+                removed[index] = true;
+                continue;
+            }
+            Liveness::step(&mut live, quad);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{format_quadruples, parse_quadruples};
+    use crate::test_util::{compile, optimise};
+
+    #[test]
+    fn test_code_after_return() {
+        assert_eq!(optimise(compile("int main() { int x = 1; return x; x = x + 1; }"), eliminate_dead_code).1, "\
+1: (func, main, , )
+2: (=, 1, , x)
+3: (return, x, , )
+");
+    }
+
+    #[test]
+    fn test_dead_assignments() {
+        // `y` is never read, and the division may fail so it stays
+        assert_eq!(optimise(compile("int main() {
+            int x = 1;
+            int z = 0;
+            int y = x * 2 + 1;
+            y = x / z;
+            y = x % 2;
+            return x;
+        }"), eliminate_dead_code).1, "\
+1: (func, main, , )
+2: (=, 1, , x)
+3: (=, 0, , z)
+4: (/, x, z, t3)
+5: (return, x, , )
+");
+    }
+
+    #[test]
+    fn test_loop_variables_stay_live() {
+        assert_eq!(optimise(compile("int main() {
+            int i = 0;
+            int s = 0;
+            while (i < 5) { s = s + i; i = i + 1; }
+            return s;
+        }"), eliminate_dead_code).1, "\
+1: (func, main, , )
+2: (=, 0, , i)
+3: (=, 0, , s)
+4: (j<, i, 5, 6)
+5: (j, , , 11)
+6: (+, s, i, t1)
+7: (=, t1, , s)
+8: (+, i, 1, t2)
+9: (=, t2, , i)
+10: (j, , , 4)
+11: (return, s, , )
+");
+    }
+
+    #[test]
+    fn test_jump_chains() {
+        let quads = compile("int main() {
+            int a = 1;
+            int x = 0;
+            if (a > 0) { if (a > 1) { x = 1; } else { x = 2; } } else { x = 3; }
+            return x;
+        }");
+        // `x = 0` is overwritten on every path, and the inner then-part
+        // used to jump to the jump at the end of the outer then-part
+        assert_eq!(optimise(quads, eliminate_dead_code).1, "\
+1: (func, main, , )
+2: (=, 1, , a)
+3: (j>, a, 0, 5)
+4: (j, , , 11)
+5: (j>, a, 1, 7)
+6: (j, , , 9)
+7: (=, 1, , x)
+8: (j, , , 12)
+9: (=, 2, , x)
+10: (j, , , 12)
+11: (=, 3, , x)
+12: (return, x, , )
+");
+    }
+
+    #[test]
+    fn test_jump_cycles() {
+        let mut quads = parse_quadruples("
+            (func, main, , )
+            (j, , , 3)
+            (j, , , 2)
+        ").unwrap();
+        // the jump to the next quadruple goes, leaving a loop on its own
+        assert_eq!(eliminate_dead_code(&mut quads), 1);
+        assert_eq!(format_quadruples(&quads), "1: (func, main, , )\n2: (j, , , 2)\n");
+    }
+}
@@ -0,0 +1,308 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use super::is_pure;
+use crate::cfg::{BlockId, Cfg};
+use crate::dataflow::{self, Chains, Liveness};
+use crate::dominators::Dominators;
+use crate::ir::{Opcode, Operand, Quadruple};
+use crate::loops::{Loop, LoopNest};
+
+/// Moves loop-invariant computations into a preheader, a new block in
+/// front of the loop header entered from outside the loop only. Jumps into
+/// the header from outside go to the preheader, back edges still go to the
+/// header.
+///
+/// A quadruple is moved if it only computes its result, its operands are
+/// constants or only assigned outside the loop or by an invariant
+/// quadruple, and moving it cannot change what the loop reads: its result
+/// is assigned nowhere else in the loop, is not live on entry to the
+/// header, and is live at no loop exit its block does not dominate.
+///
+/// Inner loops go first, so an invariant can move out of several loops.
+/// Returns the number of moves.
+pub fn hoist_loop_invariants(quads: &mut Vec<Quadruple>) -> usize {
+    let mut moves = 0;
+    // every round empties one loop, as positions change afterwards
+    loop {
+        let cfg = Cfg::build(quads);
+        let dominators = Dominators::build(&cfg);
+        let mut nest = LoopNest::build(&cfg, &dominators);
+        nest.loops.sort_by_key(|l| Reverse(l.depth));
+        let chains = Chains::build(&cfg, quads);
+        let live_in = dataflow::solve(&Liveness, &cfg, quads).ins;
+
+        let found = nest.loops.iter().filter(|l| has_preheader_position(quads, &cfg, l)).find_map(|l| {
+            let invariants = invariants(quads, &cfg, &dominators, &chains, &live_in, l);
+            (!invariants.is_empty()).then_some((l, invariants))
+        });
+        let Some((l, invariants)) = found else {
+            return moves;
+        };
+        moves += invariants.len();
+        let mut removed = vec![false; quads.len()];
+        let mut preheader = Vec::new();
+        for &index in &invariants {
+            removed[index] = true;
+            preheader.push(quads[index].clone());
+        }
+        *quads = rewrite_loop(quads, &cfg, l, &preheader, &removed, &HashMap::new());
+    }
+}
+
+/// Whether a preheader can go right in front of the header: the header
+/// does not start a function, and no block of the loop falls through into
+/// it.
+pub fn has_preheader_position(quads: &[Quadruple], cfg: &Cfg, l: &Loop) -> bool {
+    let header = &cfg.blocks[l.header];
+    quads[header.start].op != Opcode::Func && !(l.header > 0 && header.preds.contains(&(l.header - 1)) && l.blocks.contains(&(l.header - 1)))
+}
+
+/// Indices of the quadruples of `l` that can move to its preheader, each
+/// after those it depends on.
+fn invariants(
+    quads: &[Quadruple],
+    cfg: &Cfg,
+    dominators: &Dominators,
+    chains: &Chains,
+    live_in: &[BTreeSet<Operand>],
+    l: &Loop,
+) -> Vec<usize> {
+    let in_loop = |index: usize| l.blocks.contains(&cfg.block_of[index]);
+    let indices: Vec<usize> = l.blocks.iter().flat_map(|&id| cfg.blocks[id].range()).collect();
+    let mut assignments: HashMap<&Operand, usize> = HashMap::new();
+    for &index in &indices {
+        if let Some(def) = quads[index].def() {
+            *assignments.entry(def).or_default() += 1;
+        }
+    }
+    let exits: Vec<(BlockId, BlockId)> = l
+        .blocks
+        .iter()
+        .flat_map(|&id| cfg.blocks[id].succs.iter().filter(|s| !l.blocks.contains(s)).map(move |&s| (id, s)))
+        .collect();
+
+    let mut invariants: Vec<usize> = Vec::new();
+    loop {
+        let found = invariants.len();
+        for &index in &indices {
+            let quad = &quads[index];
+            let Some(def) = quad.def() else {
+                continue;
+            };
+            if invariants.contains(&index) || !is_pure(quad) {
+                continue;
+            }
+            let operands_invariant = quad.uses().into_iter().all(|operand| {
+                if let Operand::Const(_) = operand {
+                    return true;
+                }
+                // an operand read before any assignment is left alone
+                let defs = &chains.use_def[&(index + 1, operand.clone())];
+                !defs.is_empty()
+                    && (defs.iter().all(|&d| !in_loop(d - 1))
+                        || (defs.len() == 1 && defs.iter().all(|&d| invariants.contains(&(d - 1)))))
+            });
+            let safe = assignments[def] == 1
+                && !live_in[l.header].contains(def)
+                && exits
+                    .iter()
+                    .all(|&(from, to)| dominators.dominates(cfg.block_of[index], from) || !live_in[to].contains(def));
+            if operands_invariant && safe {
+                invariants.push(index);
+            }
+        }
+        if invariants.len() == found {
+            return invariants;
+        }
+    }
+}
+
+/// Gives `l` a preheader holding `preheader`, drops the quadruples marked
+/// in `removed` and places `after[i]` right behind quadruple `i`,
+/// renumbering jump targets. A jump to a dropped quadruple goes to the next
+/// one kept; jumps into the header from outside the loop go to the
+/// preheader.
+pub fn rewrite_loop(
+    quads: &[Quadruple],
+    cfg: &Cfg,
+    l: &Loop,
+    preheader: &[Quadruple],
+    removed: &[bool],
+    after: &HashMap<usize, Vec<Quadruple>>,
+) -> Vec<Quadruple> {
+    let n = quads.len();
+    let header = cfg.blocks[l.header].start;
+    let inserted = |index: usize| after.get(&index).map_or(&[][..], Vec::as_slice);
+
+    // new index of every old quadruple, or of the next one kept for dropped
+    // ones, and of the position past the end
+    let mut number = Vec::with_capacity(n + 1);
+    let mut preheader_start = 0;
+    let mut next = 0;
+    for (index, &is_removed) in removed.iter().enumerate() {
+        if index == header {
+            preheader_start = next;
+            next += preheader.len();
+        }
+        number.push(next);
+        if !is_removed {
+            next += 1;
+        }
+        next += inserted(index).len();
+    }
+    number.push(next);
+
+    let mut output = Vec::with_capacity(next);
+    for (index, quad) in quads.iter().enumerate() {
+        if index == header {
+            output.extend_from_slice(preheader);
+        }
+        if !removed[index] {
+            let mut quad = quad.clone();
+            if quad.op.is_jump() && let Operand::Label(target) = &mut quad.result && (1..=n + 1).contains(target) {
+                *target = if *target - 1 == header && !l.blocks.contains(&cfg.block_of[index]) {
+                    preheader_start + 1
+                }
+                else {
+                    number[*target - 1] + 1
+                };
+            }
+            output.push(quad);
+        }
+        output.extend_from_slice(inserted(index));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::format_quadruples;
+    use crate::test_util::{compile, optimise};
+
+    #[test]
+    fn test_hoist_from_body_and_condition() {
+        let (moves, quads) = optimise(compile("int main() {
+            int a = 2;
+            int i = 0;
+            int s = 0;
+            while (i < a * a) { s = s + (a + 1); i = i + 1; }
+            return s;
+        }"), hoist_loop_invariants);
+        assert_eq!(moves, 2);
+        assert_eq!(quads, "\
+1: (func, main, , )
+2: (=, 2, , a)
+3: (=, 0, , i)
+4: (=, 0, , s)
+5: (*, a, a, t1)
+6: (+, a, 1, t2)
+7: (j<, i, t1, 9)
+8: (j, , , 14)
+9: (+, s, t2, t3)
+10: (=, t3, , s)
+11: (+, i, 1, t4)
+12: (=, t4, , i)
+13: (j, , , 7)
+14: (return, s, , )
+");
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        // `a * b` leaves both loops, `i + b` only the inner one
+        let (moves, quads) = optimise(compile("int main() {
+            int a = 3;
+            int b = 4;
+            int i = 0;
+            int s = 0;
+            while (i < 2) {
+                int j = 0;
+                while (j < 3) { s = s + a * b + (i + b); j = j + 1; }
+                i = i + 1;
+            }
+            return s;
+        }"), hoist_loop_invariants);
+        assert_eq!(moves, 3);
+        assert_eq!(quads, "\
+1: (func, main, , )
+2: (=, 3, , a)
+3: (=, 4, , b)
+4: (=, 0, , i)
+5: (=, 0, , s)
+6: (*, a, b, t1)
+7: (j<, i, 2, 9)
+8: (j, , , 22)
+9: (=, 0, , j)
+10: (+, i, b, t3)
+11: (j<, j, 3, 13)
+12: (j, , , 19)
+13: (+, s, t1, t2)
+14: (+, t2, t3, t4)
+15: (=, t4, , s)
+16: (+, j, 1, t5)
+17: (=, t5, , j)
+18: (j, , , 11)
+19: (+, i, 1, t6)
+20: (=, t6, , i)
+21: (j, , , 7)
+22: (return, s, , )
+");
+    }
+
+    #[test]
+    fn test_unsafe_moves() {
+        // the division may fail, `x` is read after the loop although the
+        // loop may assign it not at all, and `y` is assigned twice
+        let input = "int main() {
+            int i = 0;
+            int z = 0;
+            int x = 1;
+            int y = 0;
+            while (i < 3) {
+                if (i > 5) { i = i / z; }
+                x = 7;
+                y = 1;
+                y = 2;
+                i = i + 1;
+            }
+            return x + y;
+        }";
+        let (moves, quads) = optimise(compile(input), hoist_loop_invariants);
+        assert_eq!(moves, 0);
+        assert_eq!(quads, format_quadruples(&compile(input)));
+    }
+
+    #[test]
+    fn test_jumps_into_the_header() {
+        // with `a = 2` the if jumps straight to the loop, which must go to
+        // the preheader; with `a = 5` the then part falls through into it
+        let program = "int main() {
+            int a = A;
+            int s = 0;
+            if (a > 3) { s = 1; }
+            while (s < 20) { s = s + a * 2; }
+            return s;
+        }";
+        let (moves, _) = optimise(compile(&program.replace("A", "5")), hoist_loop_invariants);
+        assert_eq!(moves, 1);
+        let (moves, quads) = optimise(compile(&program.replace("A", "2")), hoist_loop_invariants);
+        assert_eq!(moves, 1);
+        assert_eq!(quads, "\
+1: (func, main, , )
+2: (=, 2, , a)
+3: (=, 0, , s)
+4: (j>, a, 3, 6)
+5: (j, , , 7)
+6: (=, 1, , s)
+7: (*, a, 2, t1)
+8: (j<, s, 20, 10)
+9: (j, , , 13)
+10: (+, s, t1, t2)
+11: (=, t2, , s)
+12: (j, , , 8)
+13: (return, s, , )
+");
+    }
+}
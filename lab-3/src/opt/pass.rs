@@ -0,0 +1,283 @@
+use std::fmt::{self, Write};
+use std::time::{Duration, Instant};
+
+use super::temps::{recycle_temps, TempCount};
+use crate::ir::{format_quadruples, Quadruple};
+
+/// A transformation of the quadruples of a whole program.
+pub trait Pass {
+    /// The name used on the command line and in dumps.
+    fn name(&self) -> &'static str;
+    /// Runs the pass and returns the number of changes it made.
+    fn run(&mut self, quads: &mut Vec<Quadruple>) -> usize;
+    /// Lines to add to the statistics about the last run.
+    fn details(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A pass made from one of the functions in `opt`.
+#[derive(Debug, Clone, Copy)]
+pub struct FnPass {
+    pub name: &'static str,
+    pub run: fn(&mut Vec<Quadruple>) -> usize,
+}
+
+impl Pass for FnPass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&mut self, quads: &mut Vec<Quadruple>) -> usize {
+        (self.run)(quads)
+    }
+}
+
+/// Every pass made from a function, by name.
+pub const PASSES: [FnPass; 6] = [
+    FnPass { name: "constprop", run: super::propagate_constants },
+    FnPass { name: "copyprop", run: super::propagate_copies },
+    FnPass { name: "cse", run: super::eliminate_common_subexpressions },
+    FnPass { name: "licm", run: super::hoist_loop_invariants },
+    FnPass { name: "strength", run: super::reduce_strength },
+    FnPass { name: "dce", run: super::eliminate_dead_code },
+];
+
+/// Temporary recycling, which counts the temporaries saved as changes and
+/// reports the counts of every function.
+#[derive(Debug, Default)]
+pub struct RecycleTemps {
+    /// counts of the last run
+    pub counts: Vec<TempCount>,
+}
+
+impl Pass for RecycleTemps {
+    fn name(&self) -> &'static str {
+        "temps"
+    }
+
+    fn run(&mut self, quads: &mut Vec<Quadruple>) -> usize {
+        self.counts = recycle_temps(quads);
+        self.counts.iter().map(|count| count.before - count.after).sum()
+    }
+
+    fn details(&self) -> Vec<String> {
+        self.counts.iter().map(|count| count.to_string()).collect()
+    }
+}
+
+pub fn pass_by_name(name: &str) -> Option<Box<dyn Pass>> {
+    if name == "temps" {
+        return Some(Box::new(RecycleTemps::default()));
+    }
+    PASSES.iter().find(|pass| pass.name == name).map(|&pass| Box::new(pass) as Box<dyn Pass>)
+}
+
+/// What one run of a pass did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassStats {
+    pub name: &'static str,
+    pub changes: usize,
+    pub time: Duration,
+    /// quadruples after the pass
+    pub size: usize,
+    /// what the pass reported about the run
+    pub details: Vec<String>,
+}
+
+/// Runs passes in order, dumping the quadruples around them on request and
+/// recording what each one did.
+#[derive(Default)]
+pub struct PassManager {
+    pub passes: Vec<Box<dyn Pass>>,
+    /// names of the passes to dump the quadruples after
+    pub print_after: Vec<String>,
+    pub print_before_all: bool,
+    /// one entry per pass run, in order
+    pub stats: Vec<PassStats>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pipeline of an optimisation level: none for 0, cleanups within
+    /// and across blocks for 1, and loop optimisations, a second round of
+    /// cleanups and temporary recycling for 2 and above.
+    pub fn for_level(level: u32) -> Self {
+        let names: &[&str] = match level {
+            0 => &[],
+            1 => &["constprop", "copyprop", "cse", "dce"],
+            _ => &["constprop", "copyprop", "cse", "licm", "strength", "constprop", "copyprop", "dce", "temps"],
+        };
+        let mut manager = Self::new();
+        for name in names {
+            manager.add(pass_by_name(name).unwrap());
+        }
+        manager
+    }
+
+    pub fn add(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    /// Runs every pass over `quads`, writing the requested dumps to `dump`.
+    pub fn run(&mut self, quads: &mut Vec<Quadruple>, dump: &mut impl Write) -> fmt::Result {
+        for pass in &mut self.passes {
+            let name = pass.name();
+            if self.print_before_all {
+                writeln!(dump, "=== Before {} ===", name)?;
+                dump.write_str(&format_quadruples(quads))?;
+            }
+            let start = Instant::now();
+            let changes = pass.run(quads);
+            let time = start.elapsed();
+            if self.print_after.iter().any(|n| n == name) {
+                writeln!(dump, "=== After {} ({} changes) ===", name, changes)?;
+                dump.write_str(&format_quadruples(quads))?;
+            }
+            self.stats.push(PassStats {
+                name,
+                changes,
+                time,
+                size: quads.len(),
+                details: pass.details(),
+            });
+        }
+        Ok(())
+    }
+
+    /// A table of the recorded statistics, one line per pass run followed by
+    /// the details it reported.
+    pub fn report(&self) -> String {
+        let mut report = format!("{:<10} {:>8} {:>6} {:>12}\n", "pass", "changes", "quads", "time");
+        for stats in &self.stats {
+            writeln!(
+                report,
+                "{:<10} {:>8} {:>6} {:>12}",
+                stats.name,
+                stats.changes,
+                stats.size,
+                format!("{:.3?}", stats.time),
+            )
+            .unwrap();
+            for line in &stats.details {
+                writeln!(report, "    {}", line).unwrap();
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::test_util::compile;
+
+    const PROGRAMS: [&str; 2] = ["
+        int square(int x) { return x * x; }
+        int main() {
+            int n = 6;
+            int i = 0;
+            int s = 0;
+            while (i < n) {
+                int k = n * 2;
+                if (i > 2 && i != 4) { s = s + i * 4 + k; } else { s = s - square(i); }
+                i = i + 1;
+            }
+            return s;
+        }", "
+        int fact(int n) { if (n <= 1) { return 1; } return n * fact(n - 1); }
+        int main() {
+            int i = 0;
+            int s = 0;
+            while (i < 5) {
+                int j = 0;
+                while (j < i) { s = s + fact(j) * 2 + i * 3; j = j + 1; }
+                if (!(s > 40)) { s = s + 1; }
+                i = i + 1;
+            }
+            return s - -1;
+        }"];
+
+    #[test]
+    fn test_levels_keep_results() {
+        for program in PROGRAMS {
+            let quads = compile(program);
+            let expected = Interpreter::new(&quads).run();
+            let mut sizes = Vec::new();
+            for level in 0..=2 {
+                let mut optimised = quads.clone();
+                PassManager::for_level(level).run(&mut optimised, &mut String::new()).unwrap();
+                assert_eq!(Interpreter::new(&optimised).run(), expected, "-O{}", level);
+                sizes.push(optimised.len());
+            }
+            assert_eq!(sizes[0], quads.len());
+            assert!(sizes[1] < sizes[0]);
+        }
+    }
+
+    #[test]
+    fn test_dumps_and_stats() {
+        let mut quads = compile("int main() { int a = 2; int b = a * 3; return b; }");
+        let mut manager = PassManager::new();
+        manager.add(pass_by_name("constprop").unwrap());
+        manager.add(pass_by_name("dce").unwrap());
+        manager.print_after = vec!["dce".to_string()];
+        let mut dump = String::new();
+        manager.run(&mut quads, &mut dump).unwrap();
+        assert_eq!(dump, "\
+=== After dce (3 changes) ===
+1: (func, main, , )
+2: (return, 6, , )
+");
+        let runs: Vec<_> = manager.stats.iter().map(|s| (s.name, s.changes, s.size)).collect();
+        assert_eq!(runs, vec![("constprop", 3, 5), ("dce", 3, 2)]);
+        let report = manager.report();
+        assert!(report.starts_with("pass        changes  quads         time\nconstprop         3      5 "));
+
+        let mut manager = PassManager::new();
+        manager.add(pass_by_name("dce").unwrap());
+        manager.print_before_all = true;
+        let mut dump = String::new();
+        manager.run(&mut quads, &mut dump).unwrap();
+        assert_eq!(dump, "\
+=== Before dce ===
+1: (func, main, , )
+2: (return, 6, , )
+");
+    }
+
+    #[test]
+    fn test_temps_details() {
+        let mut quads = compile("int main() { int a = 2; int b = (a + 1) * (a - 1); return b; }");
+        let mut manager = PassManager::new();
+        manager.add(pass_by_name("temps").unwrap());
+        manager.run(&mut quads, &mut String::new()).unwrap();
+        assert_eq!(manager.stats[0].changes, 1);
+        assert_eq!(manager.stats[0].details, vec!["main: 3 -> 2 temporaries"]);
+        assert!(manager.report().ends_with("\n    main: 3 -> 2 temporaries\n"));
+    }
+
+    #[test]
+    fn test_custom_pass() {
+        struct Nop;
+        impl Pass for Nop {
+            fn name(&self) -> &'static str {
+                "nop"
+            }
+
+            fn run(&mut self, _: &mut Vec<Quadruple>) -> usize {
+                0
+            }
+        }
+
+        let mut manager = PassManager::new();
+        manager.add(Box::new(Nop));
+        manager.run(&mut compile("int main() { return 0; }"), &mut String::new()).unwrap();
+        assert_eq!(manager.stats[0].name, "nop");
+        assert!(pass_by_name("nop").is_none());
+    }
+}
@@ -0,0 +1,375 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::licm::{has_preheader_position, rewrite_loop};
+use crate::cfg::Cfg;
+use crate::dataflow::Chains;
+use crate::dominators::Dominators;
+use crate::ir::{BinOp, Opcode, Operand, Quadruple};
+use crate::loops::{Loop, LoopNest};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InductionVariable {
+    /// `var` is assigned once per iteration, to itself plus `step`, by the
+    /// quadruple at index `update`
+    Basic { var: Operand, step: i32, update: usize },
+    /// `var = basic * scale + offset` right after its only assignment in the
+    /// loop
+    Derived {
+        var: Operand,
+        basic: Operand,
+        scale: i32,
+        offset: i32,
+    },
+}
+
+impl InductionVariable {
+    pub fn var(&self) -> &Operand {
+        match self {
+            InductionVariable::Basic { var, .. } | InductionVariable::Derived { var, .. } => var,
+        }
+    }
+}
+
+impl std::fmt::Display for InductionVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InductionVariable::Basic { var, step, update } => {
+                write!(f, "{}: basic, step {} at {}", var, step, update + 1)
+            }
+            InductionVariable::Derived { var, basic, scale, offset } => {
+                write!(f, "{}: {} * {} + {}", var, basic, scale, offset)
+            }
+        }
+    }
+}
+
+/// `(basic, scale, offset)` of a basic or derived induction variable.
+fn family(iv: &InductionVariable) -> (&Operand, i32, i32) {
+    match iv {
+        InductionVariable::Basic { var, .. } => (var, 1, 0),
+        InductionVariable::Derived { basic, scale, offset, .. } => (basic, *scale, *offset),
+    }
+}
+
+/// The variable and constant of `x + c`, `c + x` and `x - c`, with `c`
+/// negated for the last.
+fn plus_constant(quad: &Quadruple) -> Option<(&Operand, i32)> {
+    match (quad.op, &quad.arg1, &quad.arg2) {
+        (Opcode::Binary(BinOp::Add), Operand::Const(c), x) | (Opcode::Binary(BinOp::Add), x, Operand::Const(c)) if !matches!(x, Operand::Const(_)) => {
+            Some((x, *c))
+        }
+        (Opcode::Binary(BinOp::Sub), x, Operand::Const(c)) if !matches!(x, Operand::Const(_)) => Some((x, c.wrapping_neg())),
+        _ => None,
+    }
+}
+
+/// The variable and constant of `x * c` and `c * x`.
+fn times_constant(quad: &Quadruple) -> Option<(&Operand, i32)> {
+    match (quad.op, &quad.arg1, &quad.arg2) {
+        (Opcode::Binary(BinOp::Mul), Operand::Const(c), x) | (Opcode::Binary(BinOp::Mul), x, Operand::Const(c)) if !matches!(x, Operand::Const(_)) => {
+            Some((x, *c))
+        }
+        _ => None,
+    }
+}
+
+/// Finds the induction variables of `l`.
+///
+/// A basic one is assigned only by `i = i + c`, or by `i = t` right after
+/// `t = i + c` in the same block as codegen emits it. A derived one is
+/// assigned only once, by an addition of or multiplication by a constant, a
+/// negation or a copy of another induction variable. An operand that is
+/// itself derived must come from the same block with its basic variable
+/// not updated in between.
+pub fn induction_variables(quads: &[Quadruple], cfg: &Cfg, chains: &Chains, l: &Loop) -> Vec<InductionVariable> {
+    let indices: Vec<usize> = l.blocks.iter().flat_map(|&id| cfg.blocks[id].range()).collect();
+    let mut assignment: HashMap<&Operand, Option<usize>> = HashMap::new();
+    for &index in &indices {
+        if let Some(def) = quads[index].def() {
+            assignment.entry(def).and_modify(|only| *only = None).or_insert(Some(index));
+        }
+    }
+    let only_assignment = |operand: &Operand| assignment.get(operand).copied().flatten();
+
+    let mut ivs: Vec<InductionVariable> = Vec::new();
+    for &index in &indices {
+        let quad = &quads[index];
+        let Some(var) = quad.def() else {
+            continue;
+        };
+        if only_assignment(var) != Some(index) {
+            continue;
+        }
+        let step = match plus_constant(quad) {
+            Some((x, c)) if x == var => Some(c),
+            _ if quad.op == Opcode::Assign => {
+                // `t = i + c` reaching `i = t` from earlier in the block
+                let defs = chains.use_def.get(&(index + 1, quad.arg1.clone()));
+                match defs.map(|defs| defs.iter().copied().collect::<Vec<_>>()).as_deref() {
+                    Some(&[d]) if cfg.block_of[d - 1] == cfg.block_of[index] && d - 1 < index => {
+                        plus_constant(&quads[d - 1]).filter(|(x, _)| *x == var).map(|(_, c)| c)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(step) = step {
+            ivs.push(InductionVariable::Basic { var: var.clone(), step, update: index });
+        }
+    }
+
+    loop {
+        let found = ivs.len();
+        for &index in &indices {
+            let quad = &quads[index];
+            let Some(var) = quad.def() else {
+                continue;
+            };
+            if only_assignment(var) != Some(index) || ivs.iter().any(|iv| iv.var() == var) {
+                continue;
+            }
+            let (operand, scale, offset) = match quad.op {
+                Opcode::Assign => (&quad.arg1, 1, 0),
+                Opcode::Neg => (&quad.arg1, -1, 0),
+                _ => match (plus_constant(quad), times_constant(quad)) {
+                    (Some((x, c)), _) => (x, 1, c),
+                    (_, Some((x, c))) => (x, c, 0),
+                    _ => continue,
+                },
+            };
+            let Some(iv) = ivs.iter().find(|iv| iv.var() == operand) else {
+                continue;
+            };
+            let (basic, s, o) = family(iv);
+            if let InductionVariable::Derived { .. } = iv {
+                let def = only_assignment(operand).unwrap();
+                let update = ivs.iter().find_map(|iv| match iv {
+                    InductionVariable::Basic { var, update, .. } if var == basic => Some(*update),
+                    _ => None,
+                });
+                let updated_between = update.is_some_and(|u| def < u && u < index);
+                if cfg.block_of[def] != cfg.block_of[index] || def > index || updated_between {
+                    continue;
+                }
+            }
+            ivs.push(InductionVariable::Derived {
+                var: var.clone(),
+                basic: basic.clone(),
+                scale: s.wrapping_mul(scale),
+                offset: o.wrapping_mul(scale).wrapping_add(offset),
+            });
+        }
+        if ivs.len() == found {
+            return ivs;
+        }
+    }
+}
+
+/// Applies algebraic identities: `x + 0`, `x - 0`, `x * 1` and `x / 1`
+/// become `x`, `x * 0` becomes `0` and `x * 2` becomes `x + x`, with the
+/// constant on either side where the operation commutes.
+pub fn simplify(quad: &Quadruple) -> Option<Quadruple> {
+    let Opcode::Binary(op) = quad.op else {
+        return None;
+    };
+    let copy = |from: &Operand| Some(Quadruple::new(Opcode::Assign, from.clone(), Operand::Empty, quad.result.clone()));
+    let commutes = matches!(op, BinOp::Add | BinOp::Mul);
+    let (x, c) = match (&quad.arg1, &quad.arg2) {
+        (Operand::Const(_), Operand::Const(_)) => return None,
+        (x, Operand::Const(c)) => (x, *c),
+        (Operand::Const(c), x) if commutes => (x, *c),
+        _ => return None,
+    };
+    match (op, c) {
+        (BinOp::Add | BinOp::Sub, 0) | (BinOp::Mul | BinOp::Div, 1) => copy(x),
+        (BinOp::Mul, 0) => copy(&Operand::Const(0)),
+        (BinOp::Mul, 2) => Some(Quadruple::new(Opcode::Binary(BinOp::Add), x.clone(), x.clone(), quad.result.clone())),
+        _ => None,
+    }
+}
+
+/// Simplifies algebraic identities, then replaces every multiplication of
+/// a basic induction variable by a constant with a new temporary set before
+/// the loop and increased along with the variable.
+///
+/// Returns the number of simplified and reduced quadruples.
+pub fn reduce_strength(quads: &mut Vec<Quadruple>) -> usize {
+    let mut changes = 0;
+    for quad in quads.iter_mut() {
+        if let Some(simplified) = simplify(quad) {
+            *quad = simplified;
+            changes += 1;
+        }
+    }
+
+    // every round reduces one loop, as positions change afterwards; the
+    // multiplications move to the preheader, where an enclosing loop may
+    // reduce them in turn
+    loop {
+        let cfg = Cfg::build(quads);
+        let mut nest = LoopNest::build(&cfg, &Dominators::build(&cfg));
+        nest.loops.sort_by_key(|l| Reverse(l.depth));
+        let chains = Chains::build(&cfg, quads);
+
+        let found = nest.loops.iter().filter(|l| has_preheader_position(quads, &cfg, l)).find_map(|l| {
+            let reductions = reductions(quads, &cfg, &chains, l);
+            (!reductions.is_empty()).then_some((l, reductions))
+        });
+        let Some((l, reductions)) = found else {
+            return changes;
+        };
+        changes += reductions.len();
+
+        let first_temp = quads
+            .iter()
+            .flat_map(|q| [&q.arg1, &q.arg2, &q.result])
+            .filter_map(|operand| match operand {
+                Operand::Temp(n) => Some(n + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+        let mut preheader = Vec::new();
+        let mut after: HashMap<usize, Vec<Quadruple>> = HashMap::new();
+        for (n, (index, basic, scale, step, update)) in (first_temp..).zip(reductions) {
+            let temp = Operand::Temp(n);
+            preheader.push(Quadruple::new(Opcode::Binary(BinOp::Mul), basic, Operand::Const(scale), temp.clone()));
+            after.entry(update).or_default().push(Quadruple::new(
+                Opcode::Binary(BinOp::Add),
+                temp.clone(),
+                Operand::Const(scale.wrapping_mul(step)),
+                temp.clone(),
+            ));
+            quads[index] = Quadruple::new(Opcode::Assign, temp, Operand::Empty, quads[index].result.clone());
+        }
+        *quads = rewrite_loop(quads, &cfg, l, &preheader, &vec![false; quads.len()], &after);
+    }
+}
+
+/// The multiplications of `l` to reduce, with their basic induction
+/// variable, the constant factor, its step and the index of its update.
+fn reductions(quads: &[Quadruple], cfg: &Cfg, chains: &Chains, l: &Loop) -> Vec<(usize, Operand, i32, i32, usize)> {
+    let ivs = induction_variables(quads, cfg, chains, l);
+    let mut reductions = Vec::new();
+    for &id in &l.blocks {
+        for index in cfg.blocks[id].range() {
+            let Some((x, scale)) = times_constant(&quads[index]) else {
+                continue;
+            };
+            let basic = ivs.iter().find_map(|iv| match iv {
+                InductionVariable::Basic { var, step, update } if var == x => Some((*step, *update)),
+                _ => None,
+            });
+            // the preheader reads the variable, so it must be assigned
+            // before the loop
+            let defs = &chains.use_def[&(index + 1, x.clone())];
+            let assigned_before = defs.iter().any(|&d| !l.blocks.contains(&cfg.block_of[d - 1]));
+            if let Some((step, update)) = basic && assigned_before {
+                reductions.push((index, x.clone(), scale, step, update));
+            }
+        }
+    }
+    reductions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::parse_quadruples;
+    use crate::test_util::{compile, optimise};
+
+    const LOOP: &str = "int main() {
+        int i = 0;
+        int s = 0;
+        while (i < 10) { int x = i * 4; s = s + x + 3; i = i + 1; }
+        return s;
+    }";
+
+    #[test]
+    fn test_induction_variables() {
+        let quads = compile(LOOP);
+        let cfg = Cfg::build(&quads);
+        let nest = LoopNest::build(&cfg, &Dominators::build(&cfg));
+        let ivs = induction_variables(&quads, &cfg, &Chains::build(&cfg, &quads), &nest.loops[0]);
+        let ivs: Vec<String> = ivs.iter().map(|iv| iv.to_string()).collect();
+        assert_eq!(ivs, vec![
+            "i: basic, step 1 at 12",
+            "t1: i * 4 + 0",
+            "x: i * 4 + 0",
+            "t4: i * 1 + 1",
+        ]);
+    }
+
+    #[test]
+    fn test_reduce_loop() {
+        let (changes, quads) = optimise(compile(LOOP), reduce_strength);
+        assert_eq!(changes, 1);
+        assert_eq!(quads, "\
+1: (func, main, , )
+2: (=, 0, , i)
+3: (=, 0, , s)
+4: (*, i, 4, t5)
+5: (j<, i, 10, 7)
+6: (j, , , 16)
+7: (=, t5, , t1)
+8: (=, t1, , x)
+9: (+, s, x, t2)
+10: (+, t2, 3, t3)
+11: (=, t3, , s)
+12: (+, i, 1, t4)
+13: (=, t4, , i)
+14: (+, t5, 4, t5)
+15: (j, , , 5)
+16: (return, s, , )
+");
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        // `j * 8` is reduced in the inner loop, `i * 3` in the outer one
+        let (changes, _) = optimise(compile("int main() {
+            int i = 0;
+            int s = 0;
+            while (i < 4) {
+                int j = 0;
+                while (j < 3) { s = s + j * 8 - i * 3; j = j + 1; }
+                i = i + 2;
+            }
+            return s;
+        }"), reduce_strength);
+        assert_eq!(changes, 2);
+    }
+
+    #[test]
+    fn test_simplify() {
+        let (changes, quads) = optimise(parse_quadruples("
+            (func, main, , )
+            (=, 5, , x)
+            (*, x, 2, t1)
+            (*, 1, t1, t2)
+            (+, 0, t2, t3)
+            (-, t3, 0, t4)
+            (/, t4, 1, t5)
+            (*, x, 0, t6)
+            (-, 0, t6, t7)
+            (+, t5, t7, t8)
+            (return, t8, , )
+        ").unwrap(), reduce_strength);
+        assert_eq!(changes, 6);
+        assert_eq!(quads, "\
+1: (func, main, , )
+2: (=, 5, , x)
+3: (+, x, x, t1)
+4: (=, t1, , t2)
+5: (=, t2, , t3)
+6: (=, t3, , t4)
+7: (=, t4, , t5)
+8: (=, 0, , t6)
+9: (-, 0, t6, t7)
+10: (+, t5, t7, t8)
+11: (return, t8, , )
+");
+    }
+}
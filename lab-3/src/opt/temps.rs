@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::cfg::Cfg;
+use crate::dataflow::{self, Liveness};
+use crate::ir::{Operand, Quadruple};
+
+/// Distinct temporaries of a function before and after recycling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempCount {
+    pub function: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl std::fmt::Display for TempCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {} -> {} temporaries", self.function, self.before, self.after)
+    }
+}
+
+/// Renumbers the temporaries of every function so that those never live at
+/// the same time share a number, starting again from `t1` in each function.
+///
+/// Two temporaries interfere if one is assigned while the other is live
+/// afterwards. They are numbered in order, each taking the lowest number
+/// none of its interfering temporaries has.
+pub fn recycle_temps(quads: &mut [Quadruple]) -> Vec<TempCount> {
+    let cfg = Cfg::build(quads);
+    let live_out = dataflow::solve(&Liveness, &cfg, quads).outs;
+    let mut counts = Vec::with_capacity(cfg.functions.len());
+
+    for function in &cfg.functions {
+        let mut interference: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for block in &cfg.blocks[function.clone()] {
+            for quad in &quads[block.range()] {
+                for operand in quad.uses().into_iter().chain(quad.def()) {
+                    if let Operand::Temp(n) = operand {
+                        interference.entry(*n).or_default();
+                    }
+                }
+            }
+
+            let mut live = live_out[block.id].clone();
+            for quad in quads[block.range()].iter().rev() {
+                if let Some(Operand::Temp(def)) = quad.def() {
+                    for other in &live {
+                        if let Operand::Temp(other) = other && other != def {
+                            interference.get_mut(def).unwrap().insert(*other);
+                            interference.entry(*other).or_default().insert(*def);
+                        }
+                    }
+                }
+                Liveness::step(&mut live, quad);
+            }
+        }
+
+        let mut number: HashMap<usize, usize> = HashMap::new();
+        for (temp, neighbours) in &interference {
+            let taken: BTreeSet<usize> = neighbours.iter().filter_map(|n| number.get(n)).copied().collect();
+            let free = (1..).find(|n| !taken.contains(n)).unwrap();
+            number.insert(*temp, free);
+        }
+
+        let range = cfg.blocks[function.start].start..cfg.blocks[function.end - 1].end;
+        for quad in &mut quads[range] {
+            for operand in [&mut quad.arg1, &mut quad.arg2, &mut quad.result] {
+                if let Operand::Temp(n) = operand {
+                    *n = number[n];
+                }
+            }
+        }
+        counts.push(TempCount {
+            function: cfg.function_name(quads, function),
+            before: interference.len(),
+            after: number.values().collect::<BTreeSet<_>>().len(),
+        });
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::ir::parse_quadruples;
+    use crate::test_util::{compile, optimise};
+
+    #[test]
+    fn test_recycle_expression_temps() {
+        let (counts, quads) = optimise(
+            compile("int main() { int a = 2; int b = (a + 1) * (a - 1) + a * a; return b - -a; }"),
+            |quads| recycle_temps(quads),
+        );
+        assert_eq!(counts, vec![TempCount { function: "main".to_string(), before: 7, after: 2 }]);
+        assert_eq!(quads, "\
+1: (func, main, , )
+2: (=, 2, , a)
+3: (+, a, 1, t1)
+4: (-, a, 1, t2)
+5: (*, t1, t2, t1)
+6: (*, a, a, t2)
+7: (+, t1, t2, t1)
+8: (=, t1, , b)
+9: (uminus, a, , t1)
+10: (-, b, t1, t1)
+11: (return, t1, , )
+");
+    }
+
+    #[test]
+    fn test_temps_live_across_blocks_and_functions() {
+        // `t1` stays live around the loop, `t3` shares a number with `t2`,
+        // which is dead while `t3` is live, and `t5` takes that of `t1`
+        // after the loop; each function starts again from `t1`
+        let quads = parse_quadruples("
+            (func, f, , )
+            (formal, , , n)
+            (+, n, 1, t4)
+            (return, t4, , )
+            (func, main, , )
+            (=, 10, , t1)
+            (=, 0, , t2)
+            (j<=, t1, 0, 13)
+            (+, t2, t1, t3)
+            (-, t1, 1, t1)
+            (=, t3, , t2)
+            (j, , , 8)
+            (param, t2, , )
+            (call, f, 1, t5)
+            (return, t5, , )
+        ").unwrap();
+        assert_eq!(Interpreter::new(&quads).run(), Ok(56));
+        let (counts, quads) = optimise(quads, |quads| recycle_temps(quads));
+        let counts: Vec<String> = counts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(counts, vec!["f: 1 -> 1 temporaries", "main: 4 -> 2 temporaries"]);
+        assert_eq!(quads, "\
+1: (func, f, , )
+2: (formal, , , n)
+3: (+, n, 1, t1)
+4: (return, t1, , )
+5: (func, main, , )
+6: (=, 10, , t1)
+7: (=, 0, , t2)
+8: (j<=, t1, 0, 13)
+9: (+, t2, t1, t2)
+10: (-, t1, 1, t1)
+11: (=, t2, , t2)
+12: (j, , , 8)
+13: (param, t2, , )
+14: (call, f, 1, t1)
+15: (return, t1, , )
+");
+    }
+}
@@ -1,68 +1,162 @@
-use crate::token::Token;
+use crate::token::{ Keyword, Span, SpannedToken, Token };
 use crate::ast::{self, FunctionType};
 
 pub mod error;
 
+use error::ParserError;
+
+/// Tokens that can start an expression.
+fn expr_first() -> Vec<Token> {
+    vec![
+        Token::Ident(String::new()),
+        Token::Int(String::new()),
+        Token::LParam,
+        Token::Minus,
+        Token::Plus,
+        Token::Not,
+    ]
+}
+
+/// Tokens that can start a statement.
+fn stmt_first() -> Vec<Token> {
+    vec![
+        Token::Keywords(Keyword::Return),
+        Token::Keywords(Keyword::If),
+        Token::Keywords(Keyword::While),
+        Token::Keywords(Keyword::Int),
+        Token::Ident(String::new()),
+    ]
+}
+
 pub struct Parser<'a> {
-    tokens: &'a [Token],
+    tokens: &'a [SpannedToken],
     pos: usize,
+    errors: Vec<ParserError>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a [Token]) -> Self {
+    pub fn new(tokens: &'a [SpannedToken]) -> Self {
         Parser {
             tokens,
             pos: 0,
+            errors: Vec::new(),
         }
     }
 
     fn curr_token(&self) -> Option<&Token> {
-        self.tokens.get(self.pos)
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    /// Span of the current token. Past the end of the input this is the
+    /// span of the last token (normally `EOF`).
+    fn curr_span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map(|t| t.span)
+            .unwrap_or_default()
     }
 
     fn advance(&mut self) {
         self.pos += 1;
     }
 
-    fn consume_token(&mut self, expected: Token) -> Result<(), &'static str> {
+    /// Builds an `UnexpectedToken` error for the current token.
+    fn unexpected(&self, expected: Vec<Token>) -> ParserError {
+        ParserError::UnexpectedToken {
+            expected,
+            found: self.curr_token().cloned().unwrap_or(Token::EOF),
+            span: self.curr_span(),
+        }
+    }
+
+    fn consume_token(&mut self, expected: Token) -> Result<(), ParserError> {
         match self.curr_token() {
             Some(token) if token == &expected => {
                 self.advance();
                 Ok(())
             }
-            _ => Err("Unexpected token"),
+            _ => Err(self.unexpected(vec![expected])),
         }
     }
 
-    pub fn parse(&mut self) -> Result<ast::Function, &'static str> {
-        self.parse_function()
+    /// Parses the whole input, failing with every syntax error found.
+    pub fn parse(&mut self) -> Result<ast::Program, Vec<ParserError>> {
+        match self.parse_recovering() {
+            (program, errors) if errors.is_empty() => Ok(program),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    /// Parses the whole input, recovering from syntax errors instead of
+    /// stopping at the first one. Returns the (possibly partial) AST, in
+    /// which unparsable statements and conditions are replaced by
+    /// `Stmt::Error` and `Expr::Error` and functions whose header cannot be
+    /// read are left out, along with all errors found.
+    pub fn parse_recovering(&mut self) -> (ast::Program, Vec<ParserError>) {
+        self.pos = 0;
+        self.errors.clear();
+
+        let mut functions = Vec::new();
+        while self.curr_token().is_some_and(|t| *t != Token::EOF) {
+            let start = self.pos;
+            match self.parse_function() {
+                Ok(func) => functions.push(func),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize_function(start);
+                }
+            }
+        }
+        (ast::Program::new(functions), std::mem::take(&mut self.errors))
+    }
+
+    /// Recovery after an unreadable function header starting at token
+    /// `start`: skips to the next `int` outside of any braces.
+    fn synchronize_function(&mut self, start: usize) {
+        let mut depth = 0usize;
+        while let Some(token) = self.curr_token() {
+            match token {
+                Token::EOF => return,
+                Token::Keywords(Keyword::Int) if depth == 0 && self.pos > start => return,
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.advance();
+        }
     }
 
-    fn parse_function(&mut self) -> Result<ast::Function, &'static str>{
+    fn parse_function(&mut self) -> Result<ast::Function, ParserError>{
         // Parse return type
         let return_type = match self.curr_token() {
-            Some(Token::Keywords(crate::token::Keyword::Int)) => {
+            Some(Token::Keywords(Keyword::Int)) => {
                 self.advance();
                 FunctionType::Int
             }
-            _ => return Err("Expected return type (int)"),
+            _ => return Err(self.unexpected(vec![Token::Keywords(Keyword::Int)])),
         };
 
         // Parse function name
+        let span = self.curr_span();
         let function_name = match self.curr_token() {
             Some(Token::Ident(s)) => {
                 let name = s.clone();
                 self.advance();
                 name
             }
-            _ => return Err("Expected function name"),
+            _ => return Err(self.unexpected(vec![Token::Ident(String::new())])),
         };
 
-        // Parse '('
-        self.consume_token(Token::LParam)?;
-
-        // Parse ')'
-        self.consume_token(Token::RParam)?;
+        // Parse parameter list, on error skip ahead to the body
+        let params = match self.parse_params() {
+            Ok(params) => params,
+            Err(err) => {
+                self.errors.push(err);
+                self.skip_until(|t| *t == Token::LBrace);
+                Vec::new()
+            }
+        };
 
         // Parse block
         let block = self.parse_block()?;
@@ -70,46 +164,178 @@ impl<'a> Parser<'a> {
         Ok(ast::Function::new(
             return_type,
             function_name,
-            block
+            params,
+            block,
+            span
         ))
     }
 
-    fn parse_block(&mut self) -> Result<ast::Block, &'static str> {
+    fn parse_params(&mut self) -> Result<Vec<ast::Param>, ParserError> {
+        self.consume_token(Token::LParam)?;
+
+        let mut params = Vec::new();
+        if self.curr_token() == Some(&Token::RParam) {
+            self.advance(); // consume ')'
+            return Ok(params);
+        }
+
+        loop {
+            self.consume_token(Token::Keywords(Keyword::Int))?;
+            let span = self.curr_span();
+            let name = match self.curr_token() {
+                Some(Token::Ident(s)) => s.clone(),
+                _ => return Err(self.unexpected(vec![Token::Ident(String::new())])),
+            };
+            self.advance(); // consume identifier
+            params.push(ast::Param {
+                ident_type: ast::IdentType::Int,
+                name,
+                span,
+            });
+
+            match self.curr_token() {
+                Some(Token::Comma) => self.advance(),
+                Some(Token::RParam) => {
+                    self.advance();
+                    return Ok(params);
+                }
+                _ => return Err(self.unexpected(vec![Token::Comma, Token::RParam])),
+            }
+        }
+    }
+
+    fn parse_block(&mut self) -> Result<ast::Block, ParserError> {
         self.consume_token(Token::LBrace)?;
 
         let mut stmts = Vec::new();
-        while self.curr_token() != Some(&Token::RBrace) {
-            let stmt = self.parse_stmt()?;
-            stmts.push(stmt);
+        loop {
+            match self.curr_token() {
+                Some(Token::RBrace) => break,
+                None | Some(Token::EOF) => {
+                    // the block is never closed, keep what has been parsed;
+                    // the end of input is reported once for all open blocks
+                    if self.errors.last().is_none_or(|err| err.span() != self.curr_span()) {
+                        let mut expected = stmt_first();
+                        expected.push(Token::RBrace);
+                        self.errors.push(self.unexpected(expected));
+                    }
+                    return Ok(ast::Block::new(stmts));
+                }
+                _ => {}
+            }
+
+            let start = self.pos;
+            match self.parse_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize(start);
+                    let span = self.tokens[start].span.to(self.tokens[self.pos - 1].span);
+                    stmts.push(ast::Stmt::Error(span));
+                }
+            }
         }
 
         self.consume_token(Token::RBrace)?;
-        return Ok(ast::Block::new(stmts));
+        Ok(ast::Block::new(stmts))
+    }
+
+    /// Panic-mode recovery after a syntax error in the statement starting
+    /// at token `start`. Skips tokens until just after a `;`, just after a
+    /// `{ ... }` group, or before a `}` or a keyword that starts a new
+    /// statement, so that the enclosing block can carry on from there.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = 0;
+        loop {
+            match self.curr_token() {
+                None | Some(Token::EOF) => return,
+                Some(Token::Semicolon) if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                Some(Token::RBrace) if depth == 0 => return,
+                Some(Token::RBrace) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance();
+                        return;
+                    }
+                }
+                Some(Token::LBrace) => depth += 1,
+                Some(Token::Keywords(Keyword::Return | Keyword::If | Keyword::While | Keyword::Int))
+                    if depth == 0 && self.pos > start => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skips tokens until one satisfying `stop` (or `EOF`) is current.
+    fn skip_until(&mut self, stop: impl Fn(&Token) -> bool) {
+        while self.curr_token().is_some_and(|t| *t != Token::EOF && !stop(t)) {
+            self.advance();
+        }
     }
  
-    fn parse_stmt(&mut self) -> Result<ast::Stmt, &'static str> {
+    fn parse_stmt(&mut self) -> Result<ast::Stmt, ParserError> {
         match self.curr_token() {
-            Some(Token::Keywords(crate::token::Keyword::Return)) => self.parse_return_stmt(),
-            Some(Token::Keywords(crate::token::Keyword::If)) => self.parse_if_stmt(),
-            Some(Token::Keywords(crate::token::Keyword::While)) => self.parse_while_stmt(),
-            Some(Token::Keywords(crate::token::Keyword::Int)) => self.parse_declare_stmt(),
+            Some(Token::Keywords(Keyword::Return)) => self.parse_return_stmt(),
+            Some(Token::Keywords(Keyword::If)) => self.parse_if_stmt(),
+            Some(Token::Keywords(Keyword::While)) => self.parse_while_stmt(),
+            Some(Token::Keywords(Keyword::Int)) => self.parse_declare_stmt(),
             Some(Token::Ident(_)) => self.parse_assignment_stmt(),
-            _ => Err("Invalid statement"),
+            _ => {
+                let mut expected = stmt_first();
+                expected.push(Token::RBrace);
+                Err(self.unexpected(expected))
+            }
         }
     }
 // 
-    fn parse_return_stmt(&mut self) -> Result<ast::Stmt, &'static str> {
+    fn parse_return_stmt(&mut self) -> Result<ast::Stmt, ParserError> {
         self.advance(); // consume 'return'
         let expr = self.parse_expr()?;
         self.consume_token(Token::Semicolon)?;
         Ok(ast::Stmt::ReturnStmt(expr))
     }
 // 
-    fn parse_expr(&mut self) -> Result<ast::Expr, &'static str> {
-        self.parse_relation_expr()
+    fn parse_expr(&mut self) -> Result<ast::Expr, ParserError> {
+        self.parse_or_expr()
+    }
+// 
+    fn parse_or_expr(&mut self) -> Result<ast::Expr, ParserError> {
+        let mut expr = self.parse_and_expr()?;
+
+        while let Some(Token::Or) = self.curr_token() {
+            self.advance(); // consume '||'
+            let rhs = self.parse_and_expr()?;
+            expr = ast::Expr::BinaryExpr {
+                op: "||".to_string(),
+                span: expr.span().to(rhs.span()),
+                lhs: Box::new(expr),
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(expr)
+    }
+// 
+    fn parse_and_expr(&mut self) -> Result<ast::Expr, ParserError> {
+        let mut expr = self.parse_relation_expr()?;
+
+        while let Some(Token::And) = self.curr_token() {
+            self.advance(); // consume '&&'
+            let rhs = self.parse_relation_expr()?;
+            expr = ast::Expr::BinaryExpr {
+                op: "&&".to_string(),
+                span: expr.span().to(rhs.span()),
+                lhs: Box::new(expr),
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(expr)
     }
 // 
-    fn parse_relation_expr(&mut self) -> Result<ast::Expr, &'static str> {
+    fn parse_relation_expr(&mut self) -> Result<ast::Expr, ParserError> {
         let mut expr = self.parse_additive_expr()?;
         
         loop {
@@ -119,6 +345,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_additive_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: ">".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -128,6 +355,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_additive_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "<".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -137,6 +365,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_additive_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: ">=".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -146,6 +375,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_additive_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "<=".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -155,6 +385,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_additive_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "==".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -164,6 +395,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_additive_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "!=".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -174,7 +406,7 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn parse_additive_expr(&mut self) -> Result<ast::Expr, &'static str> {
+    fn parse_additive_expr(&mut self) -> Result<ast::Expr, ParserError> {
         let mut expr = self.parse_multiplicative_expr()?;
         
         loop {
@@ -184,6 +416,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_multiplicative_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "+".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -193,6 +426,7 @@ impl<'a> Parser<'a> {
                     let rhs = self.parse_multiplicative_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "-".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -203,16 +437,37 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 // 
-    fn parse_multiplicative_expr(&mut self) -> Result<ast::Expr, &'static str> {
-        let mut expr = self.parse_primary_expr()?;
+    fn parse_multiplicative_expr(&mut self) -> Result<ast::Expr, ParserError> {
+        let mut expr = self.parse_unary_expr()?;
         
         loop {
             match self.curr_token() {
                 Some(Token::Times) => {
                     self.advance(); // consume '*'
-                    let rhs = self.parse_primary_expr()?;
+                    let rhs = self.parse_unary_expr()?;
                     expr = ast::Expr::BinaryExpr {
                         op: "*".to_string(),
+                        span: expr.span().to(rhs.span()),
+                        lhs: Box::new(expr),
+                        rhs: Box::new(rhs),
+                    };
+                }
+                Some(Token::Divide) => {
+                    self.advance(); // consume '/'
+                    let rhs = self.parse_unary_expr()?;
+                    expr = ast::Expr::BinaryExpr {
+                        op: "/".to_string(),
+                        span: expr.span().to(rhs.span()),
+                        lhs: Box::new(expr),
+                        rhs: Box::new(rhs),
+                    };
+                }
+                Some(Token::Modulo) => {
+                    self.advance(); // consume '%'
+                    let rhs = self.parse_unary_expr()?;
+                    expr = ast::Expr::BinaryExpr {
+                        op: "%".to_string(),
+                        span: expr.span().to(rhs.span()),
                         lhs: Box::new(expr),
                         rhs: Box::new(rhs),
                     };
@@ -223,17 +478,57 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 // 
-    fn parse_primary_expr(&mut self) -> Result<ast::Expr, &'static str> {
+    fn parse_unary_expr(&mut self) -> Result<ast::Expr, ParserError> {
+        let span = self.curr_span();
+        let op = match self.curr_token() {
+            Some(Token::Minus) => "-",
+            Some(Token::Plus) => "+",
+            Some(Token::Not) => "!",
+            _ => return self.parse_primary_expr(),
+        };
+        self.advance(); // consume operator
+
+        // a negated literal is read as a single number, which is the only
+        // way to write i32::MIN
+        if let (Some(Token::Int(s)), "-") = (self.curr_token(), op) {
+            let literal = format!("-{}", s);
+            let span = span.to(self.curr_span());
+            let num = literal.parse::<i32>().map_err(|_| ParserError::InvalidInteger {
+                literal,
+                span,
+            })?;
+            self.advance();
+            return Ok(ast::Expr::Number(num, span));
+        }
+
+        let operand = self.parse_unary_expr()?;
+        Ok(ast::Expr::UnaryExpr {
+            op: op.to_string(),
+            span: span.to(operand.span()),
+            operand: Box::new(operand),
+        })
+    }
+// 
+    fn parse_primary_expr(&mut self) -> Result<ast::Expr, ParserError> {
+        let span = self.curr_span();
         match self.curr_token() {
             Some(Token::Int(s)) => {
-                let num = s.parse::<i32>().map_err(|_| "Invalid integer")?;
+                let num = s.parse::<i32>().map_err(|_| ParserError::InvalidInteger {
+                    literal: s.clone(),
+                    span,
+                })?;
                 self.advance();
-                Ok(ast::Expr::Number(num))
+                Ok(ast::Expr::Number(num, span))
             }
             Some(Token::Ident(s)) => {
                 let var_name = s.clone();
                 self.advance();
-                Ok(ast::Expr::Var(var_name))
+                if self.curr_token() == Some(&Token::LParam) {
+                    self.parse_call(var_name, span)
+                }
+                else {
+                    Ok(ast::Expr::Var(var_name, span))
+                }
             }
             Some(Token::LParam) => {
                 self.advance(); // consume '('
@@ -241,15 +536,40 @@ impl<'a> Parser<'a> {
                 self.consume_token(Token::RParam)?;
                 Ok(expr)
             }
-            _ => Err("Expected expression"),
+            _ => Err(self.unexpected(expr_first())),
         }
     }
 // 
-    fn parse_declare_stmt(&mut self) -> Result<ast::Stmt, &'static str> {
+    fn parse_call(&mut self, name: String, name_span: Span) -> Result<ast::Expr, ParserError> {
+        self.advance(); // consume '('
+
+        let mut args = Vec::new();
+        if self.curr_token() != Some(&Token::RParam) {
+            loop {
+                args.push(self.parse_expr()?);
+                match self.curr_token() {
+                    Some(Token::Comma) => self.advance(),
+                    Some(Token::RParam) => break,
+                    _ => return Err(self.unexpected(vec![Token::Comma, Token::RParam])),
+                }
+            }
+        }
+
+        let span = name_span.to(self.curr_span());
+        self.advance(); // consume ')'
+        Ok(ast::Expr::Call {
+            name,
+            args,
+            span,
+        })
+    }
+// 
+    fn parse_declare_stmt(&mut self) -> Result<ast::Stmt, ParserError> {
         self.advance(); // consume 'int'
+        let span = self.curr_span();
         let ident = match self.curr_token() {
             Some(Token::Ident(s)) => s.clone(),
-            _ => return Err("Expected identifier after int"),
+            _ => return Err(self.unexpected(vec![Token::Ident(String::new())])),
         };
         self.advance(); // consume identifier
         
@@ -266,13 +586,15 @@ impl<'a> Parser<'a> {
             ident_type: ast::IdentType::Int,
             ident,
             rval,
+            span,
         })
     }
 // 
-    fn parse_assignment_stmt(&mut self) -> Result<ast::Stmt, &'static str> {
+    fn parse_assignment_stmt(&mut self) -> Result<ast::Stmt, ParserError> {
+        let span = self.curr_span();
         let lval = match self.curr_token() {
             Some(Token::Ident(s)) => s.clone(),
-            _ => return Err("Expected identifier in assignment"),
+            _ => return Err(self.unexpected(vec![Token::Ident(String::new())])),
         };
         self.advance(); // consume identifier
         
@@ -284,21 +606,41 @@ impl<'a> Parser<'a> {
         Ok(ast::Stmt::AssignmentStmt {
             lval,
             rval,
+            span,
         })
     }
 // 
-    fn parse_if_stmt(&mut self) -> Result<ast::Stmt, &'static str> {
-        self.advance(); // consume 'if'
-        
+    /// Parses a parenthesised condition. A malformed expression inside the
+    /// parentheses is recorded and replaced by `Expr::Error`, so that the
+    /// body of the statement is still parsed.
+    fn parse_cond(&mut self) -> Result<ast::Expr, ParserError> {
         self.consume_token(Token::LParam)?;
+
+        match self.parse_expr() {
+            Ok(cond) => {
+                self.consume_token(Token::RParam)?;
+                Ok(cond)
+            }
+            Err(err) => {
+                let span = err.span();
+                self.errors.push(err);
+                self.skip_until(|t| matches!(t, Token::RParam | Token::LBrace | Token::RBrace | Token::Semicolon));
+                if self.curr_token() == Some(&Token::RParam) {
+                    self.advance();
+                }
+                Ok(ast::Expr::Error(span))
+            }
+        }
+    }
+// 
+    fn parse_if_stmt(&mut self) -> Result<ast::Stmt, ParserError> {
+        self.advance(); // consume 'if'
         
-        let cond = self.parse_expr()?;
-        
-        self.consume_token(Token::RParam)?;
+        let cond = self.parse_cond()?;
         
         let if_block = self.parse_block()?;
         
-        let else_stmt = if self.curr_token().is_some_and(|t| matches!(t, Token::Keywords(crate::token::Keyword::Else))) {
+        let else_stmt = if self.curr_token().is_some_and(|t| matches!(t, Token::Keywords(Keyword::Else))) {
             self.advance(); // consume 'else'
             Some(self.parse_block()?)
         } else {
@@ -312,14 +654,10 @@ impl<'a> Parser<'a> {
         })
     }
 // 
-    fn parse_while_stmt(&mut self) -> Result<ast::Stmt, &'static str> {
+    fn parse_while_stmt(&mut self) -> Result<ast::Stmt, ParserError> {
         self.advance(); // consume 'while'
         
-        self.consume_token(Token::LParam)?;
-        
-        let cond = self.parse_expr()?;
-        
-        self.consume_token(Token::RParam)?;
+        let cond = self.parse_cond()?;
         
         let block = self.parse_block()?;
         
@@ -330,3 +668,233 @@ impl<'a> Parser<'a> {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(input: &str) -> Result<ast::Program, ParserError> {
+        let tokens = Lexer::new(input).to_tokens().unwrap();
+        Parser::new(&tokens).parse().map_err(|errors| errors[0].clone())
+    }
+
+    fn parse_recovering(input: &str) -> (ast::Program, Vec<ParserError>) {
+        let tokens = Lexer::new(input).to_tokens().unwrap();
+        Parser::new(&tokens).parse_recovering()
+    }
+
+    #[test]
+    fn test_parse_ok() {
+        let program = parse("int main() { int x = 1; while (x < 3) { x = x + 1; } return x; }").unwrap();
+        assert_eq!(program.functions.len(), 1);
+        assert_eq!(program.functions[0].name, "main");
+        assert_eq!(program.functions[0].block.stmts.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_expected_semicolon() {
+        let err = parse("int main() {\n  int x = 1\n}").unwrap_err();
+        assert_eq!(err, ParserError::UnexpectedToken {
+            expected: vec![Token::Semicolon],
+            found: Token::RBrace,
+            span: err.span(),
+        });
+        assert_eq!(err.to_string(), "expected `;`, found `}` at line 3, column 1");
+    }
+
+    #[test]
+    fn test_parse_expected_expression() {
+        let err = parse("int main() { return ; }").unwrap_err();
+        assert_eq!(err.to_string(),
+            "expected one of identifier, integer literal, `(`, `-`, `+`, `!`, found `;` at line 1, column 21");
+    }
+
+    #[test]
+    fn test_parse_unclosed_block() {
+        let err = parse("int main() { return 0;").unwrap_err();
+        match err {
+            ParserError::UnexpectedToken { expected, found, .. } => {
+                assert!(expected.contains(&Token::RBrace));
+                assert_eq!(found, Token::EOF);
+            }
+            _ => panic!("Expected UnexpectedToken error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_integer() {
+        let err = parse("int main() { return 2147483648; }").unwrap_err();
+        assert!(matches!(err, ParserError::InvalidInteger { ref literal, .. } if literal == "2147483648"));
+    }
+
+    #[test]
+    fn test_recover_multiple_errors() {
+        let (program, errors) = parse_recovering("int main() {
+            int x = ;
+            x = 1
+            int y = 2;
+            y = (x + ;
+            return x;
+        }");
+        let lines: Vec<_> = errors.iter().map(|e| e.span().start.line).collect();
+        assert_eq!(lines, vec![2, 4, 5]);
+
+        let stmts = &program.functions[0].block.stmts;
+        assert_eq!(stmts.len(), 5);
+        assert!(matches!(stmts[0], ast::Stmt::Error(_)));
+        assert!(matches!(stmts[1], ast::Stmt::Error(_)));
+        assert!(matches!(stmts[2], ast::Stmt::DeclareStmt { ref ident, .. } if ident == "y"));
+        assert!(matches!(stmts[3], ast::Stmt::Error(_)));
+        assert!(matches!(stmts[4], ast::Stmt::ReturnStmt(_)));
+    }
+
+    #[test]
+    fn test_recover_in_nested_block() {
+        let (program, errors) = parse_recovering("int main() {
+            while (x > ) {
+                x = x - ;
+                else
+            }
+            while x { x = 1; }
+            return 0;
+        }");
+        assert_eq!(errors.len(), 4);
+
+        let stmts = &program.functions[0].block.stmts;
+        assert_eq!(stmts.len(), 3);
+        match &stmts[0] {
+            ast::Stmt::WhileStmt { cond, block } => {
+                assert!(matches!(cond, ast::Expr::Error(_)));
+                assert_eq!(block.stmts.len(), 2);
+                assert!(block.stmts.iter().all(|s| matches!(s, ast::Stmt::Error(_))));
+            }
+            _ => panic!("Expected WhileStmt"),
+        }
+        assert!(matches!(stmts[1], ast::Stmt::Error(_)));
+        assert!(matches!(stmts[2], ast::Stmt::ReturnStmt(_)));
+    }
+
+    #[test]
+    fn test_recover_unclosed_function() {
+        let (program, errors) = parse_recovering("int main( {\n int x = 1;");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(program.functions[0].block.stmts.len(), 1);
+    }
+
+    #[test]
+    fn test_recover_nested_unclosed_blocks() {
+        let (program, errors) = parse_recovering("int main() { if (x) { while (x) { return 0;");
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "expected one of `return`, `if`, `while`, `int`, identifier, `}`, found end of file at line 1, column 44",
+        ]);
+        assert_eq!(program.functions[0].block.stmts.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_functions_and_calls() {
+        let program = parse("int add(int a, int b) { return a + b; }
+            int main() { return add(1, add(2, 3)) * zero(); }
+            int zero() { return 0; }").unwrap();
+        let names: Vec<_> = program.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["add", "main", "zero"]);
+        let params: Vec<_> = program.functions[0].params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(params, vec!["a", "b"]);
+
+        match &program.functions[1].block.stmts[0] {
+            ast::Stmt::ReturnStmt(ast::Expr::BinaryExpr { lhs, rhs, .. }) => {
+                match lhs.as_ref() {
+                    ast::Expr::Call { name, args, .. } => {
+                        assert_eq!(name, "add");
+                        assert_eq!(args.len(), 2);
+                        assert!(matches!(args[1], ast::Expr::Call { .. }));
+                    }
+                    _ => panic!("Expected Call"),
+                }
+                assert!(matches!(rhs.as_ref(), ast::Expr::Call { args, .. } if args.is_empty()));
+            }
+            _ => panic!("Expected ReturnStmt"),
+        }
+    }
+
+    #[test]
+    fn test_parse_bad_params() {
+        let err = parse("int f(int a b) { return a; }").unwrap_err();
+        assert_eq!(err.to_string(), "expected one of `,`, `)`, found identifier `b` at line 1, column 13");
+    }
+
+    #[test]
+    fn test_recover_bad_function_header() {
+        let (program, errors) = parse_recovering("int 1() { return 0; }
+            int f(int) { return 1; }
+            int main() { return f(); }");
+        assert_eq!(errors.len(), 2);
+        let names: Vec<_> = program.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["f", "main"]);
+    }
+
+    #[test]
+    fn test_parse_unary() {
+        let program = parse("int main() { return -2147483648 - -x * !+y; }").unwrap();
+        let expr = match &program.functions[0].block.stmts[0] {
+            ast::Stmt::ReturnStmt(expr) => expr,
+            _ => panic!("Expected ReturnStmt"),
+        };
+        let (lhs, rhs) = match expr {
+            ast::Expr::BinaryExpr { op, lhs, rhs, .. } if op == "-" => (lhs, rhs),
+            _ => panic!("Expected subtraction"),
+        };
+        assert!(matches!(lhs.as_ref(), ast::Expr::Number(i32::MIN, _)));
+        let (lhs, rhs) = match rhs.as_ref() {
+            ast::Expr::BinaryExpr { op, lhs, rhs, .. } if op == "*" => (lhs, rhs),
+            _ => panic!("Expected multiplication"),
+        };
+        assert!(matches!(lhs.as_ref(), ast::Expr::UnaryExpr { op, operand, .. }
+            if op == "-" && matches!(operand.as_ref(), ast::Expr::Var(..))));
+        match rhs.as_ref() {
+            ast::Expr::UnaryExpr { op, operand, .. } if op == "!" => {
+                assert!(matches!(operand.as_ref(), ast::Expr::UnaryExpr { op, .. } if op == "+"));
+            }
+            _ => panic!("Expected logical not"),
+        }
+    }
+
+    #[test]
+    fn test_parse_negative_literal_out_of_range() {
+        let err = parse("int main() { return -2147483649; }").unwrap_err();
+        assert!(matches!(err, ParserError::InvalidInteger { ref literal, .. } if literal == "-2147483649"));
+    }
+
+    #[test]
+    fn test_parse_multiplicative() {
+        let program = parse("int main() { return a - b / c % d * e; }").unwrap();
+        let mut ops = Vec::new();
+        let mut expr = match &program.functions[0].block.stmts[0] {
+            ast::Stmt::ReturnStmt(ast::Expr::BinaryExpr { op, rhs, .. }) => {
+                ops.push(op.clone());
+                rhs.as_ref()
+            }
+            _ => panic!("Expected ReturnStmt"),
+        };
+        // left associative: ((b / c) % d) * e
+        while let ast::Expr::BinaryExpr { op, lhs, .. } = expr {
+            ops.push(op.clone());
+            expr = lhs.as_ref();
+        }
+        assert_eq!(ops, vec!["-", "*", "%", "/"]);
+    }
+
+    #[test]
+    fn test_parse_logical() {
+        let program = parse("int main() { if (a > 0 || !b && c) { return 1; } return 0; }").unwrap();
+        match &program.functions[0].block.stmts[0] {
+            ast::Stmt::IfStmt { cond: ast::Expr::BinaryExpr { op, lhs, rhs, .. }, .. } => {
+                assert_eq!(op, "||");
+                assert!(matches!(lhs.as_ref(), ast::Expr::BinaryExpr { op, .. } if op == ">"));
+                assert!(matches!(rhs.as_ref(), ast::Expr::BinaryExpr { op, .. } if op == "&&"));
+            }
+            _ => panic!("Expected IfStmt"),
+        }
+    }
+}
@@ -1,3 +1,58 @@
+use std::fmt;
+
+use crate::token::{Span, Token};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParserError {
-    FunctionError,
+    /// The parser wanted one of `expected` but saw `found`. Identifier and
+    /// integer entries in `expected` carry an empty string and stand for
+    /// any identifier or integer literal.
+    UnexpectedToken {
+        expected: Vec<Token>,
+        found: Token,
+        span: Span,
+    },
+    /// An integer literal that does not fit into `i32`.
+    InvalidInteger {
+        literal: String,
+        span: Span,
+    },
 }
+
+impl ParserError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::UnexpectedToken { span, .. } => *span,
+            ParserError::InvalidInteger { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParserError::UnexpectedToken { expected, found, .. } => {
+                write!(f, "expected ")?;
+                match expected.as_slice() {
+                    [] => write!(f, "nothing")?,
+                    [one] => write!(f, "{}", one)?,
+                    [init @ .., last] => {
+                        write!(f, "one of ")?;
+                        for t in init {
+                            write!(f, "{}, ", t)?;
+                        }
+                        write!(f, "{}", last)?;
+                    }
+                }
+                write!(f, ", found {}", found)?;
+            }
+            ParserError::InvalidInteger { literal, .. } => {
+                write!(f, "integer literal `{}` is out of range", literal)?;
+            }
+        }
+        let pos = self.span().start;
+        write!(f, " at line {}, column {}", pos.line, pos.column)
+    }
+}
+
+impl std::error::Error for ParserError {}
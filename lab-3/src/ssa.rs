@@ -0,0 +1,455 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::cfg::{BlockId, Cfg};
+use crate::dataflow::{self, Liveness};
+use crate::dominators::Dominators;
+use crate::ir::{Opcode, Operand, Quadruple};
+
+/// `result = phi(B<pred>: arg, ...)`, choosing `arg` by the block control
+/// came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub result: Operand,
+    /// one argument per reachable predecessor, in the order of `preds`
+    pub args: Vec<(BlockId, Operand)>,
+}
+
+impl std::fmt::Display for Phi {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let args: Vec<_> = self.args.iter().map(|(pred, arg)| format!("B{}: {}", pred, arg)).collect();
+        write!(f, "{} = phi({})", self.result, args.join(", "))
+    }
+}
+
+/// Static single assignment form of a list of quadruples.
+///
+/// Every variable and temporary is split into versions `x#1`, `x#2`, ...,
+/// each assigned exactly once, and `x#0` is the value on entry to the
+/// function. Phi functions sit at the start of blocks where versions meet.
+/// `quads` keeps the layout of the original, so `cfg` still describes it
+/// and jump targets are unchanged. Blocks that cannot be reached are left
+/// as they were.
+#[derive(Debug, Clone)]
+pub struct Ssa {
+    pub cfg: Cfg,
+    pub dominators: Dominators,
+    /// phi functions at the start of every block
+    pub phis: Vec<Vec<Phi>>,
+    pub quads: Vec<Quadruple>,
+}
+
+fn version(base: &Operand, n: usize) -> Operand {
+    Operand::Var(format!("{}#{}", base, n))
+}
+
+/// Versions of every variable during renaming.
+#[derive(Debug, Default)]
+struct Versions {
+    /// versions visible in the current block, innermost last
+    stacks: HashMap<Operand, Vec<Operand>>,
+    /// last version handed out
+    counters: HashMap<Operand, usize>,
+}
+
+impl Versions {
+    fn current(&self, base: &Operand) -> Operand {
+        match self.stacks.get(base).and_then(|stack| stack.last()) {
+            Some(name) => name.clone(),
+            None => version(base, 0),
+        }
+    }
+
+    fn define(&mut self, base: &Operand) -> Operand {
+        let counter = self.counters.entry(base.clone()).or_default();
+        *counter += 1;
+        let name = version(base, *counter);
+        self.stacks.entry(base.clone()).or_default().push(name.clone());
+        name
+    }
+}
+
+impl Ssa {
+    /// Builds pruned SSA: a phi is only placed where the variable is live.
+    pub fn build(quads: &[Quadruple]) -> Ssa {
+        let cfg = Cfg::build(quads);
+        let dominators = Dominators::build(&cfg);
+        let live_in = dataflow::solve(&Liveness, &cfg, quads).ins;
+        let mut phis = vec![Vec::new(); cfg.blocks.len()];
+        // variable each phi is for, before renaming
+        let mut phi_vars: Vec<Vec<Operand>> = vec![Vec::new(); cfg.blocks.len()];
+
+        for rpo in &dominators.order {
+            // blocks assigning every variable, in a stable order
+            let mut def_sites: BTreeMap<Operand, BTreeSet<BlockId>> = BTreeMap::new();
+            for &id in rpo {
+                for quad in &quads[cfg.blocks[id].range()] {
+                    if let Some(def) = quad.def() {
+                        def_sites.entry(def.clone()).or_default().insert(id);
+                    }
+                }
+            }
+
+            for (var, sites) in def_sites {
+                let mut worklist: Vec<BlockId> = sites.iter().copied().collect();
+                let mut has_phi = BTreeSet::new();
+                while let Some(id) = worklist.pop() {
+                    for &join in &dominators.frontier[id] {
+                        if !live_in[join].contains(&var) || !has_phi.insert(join) {
+                            continue;
+                        }
+                        let args = cfg.blocks[join]
+                            .preds
+                            .iter()
+                            .filter(|&&pred| dominators.order.iter().any(|rpo| rpo.contains(&pred)))
+                            .map(|&pred| (pred, Operand::Empty))
+                            .collect();
+                        phis[join].push(Phi { result: var.clone(), args });
+                        phi_vars[join].push(var.clone());
+                        if !sites.contains(&join) {
+                            worklist.push(join);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut ssa = Ssa {
+            cfg,
+            dominators,
+            phis,
+            quads: quads.to_vec(),
+        };
+        for rpo in ssa.dominators.order.clone() {
+            ssa.rename(rpo[0], &phi_vars, &mut Versions::default());
+        }
+        ssa
+    }
+
+    /// Renames the block `id` and, through the dominator tree, every block
+    /// it dominates.
+    fn rename(&mut self, id: BlockId, phi_vars: &[Vec<Operand>], versions: &mut Versions) {
+        let mut defined = Vec::new();
+        for (phi, var) in self.phis[id].iter_mut().zip(&phi_vars[id]) {
+            phi.result = versions.define(var);
+            defined.push(var.clone());
+        }
+        for index in self.cfg.blocks[id].range() {
+            let quad = &mut self.quads[index];
+            for operand in quad.uses_mut() {
+                if matches!(operand, Operand::Var(_) | Operand::Temp(_)) {
+                    *operand = versions.current(operand);
+                }
+            }
+            if let Some(def) = quad.def().cloned() {
+                quad.result = versions.define(&def);
+                defined.push(def);
+            }
+        }
+
+        for succ in self.cfg.blocks[id].succs.clone() {
+            for (phi, var) in self.phis[succ].iter_mut().zip(&phi_vars[succ]) {
+                for (pred, arg) in &mut phi.args {
+                    if *pred == id {
+                        *arg = versions.current(var);
+                    }
+                }
+            }
+        }
+
+        for child in self.dominators.children[id].clone() {
+            self.rename(child, phi_vars, versions);
+        }
+        for var in defined {
+            versions.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+
+    /// Translates out of SSA, replacing every phi with copies at the end of
+    /// its predecessors. A conditional jump to a block with phis is sent to
+    /// a new block holding the copies, placed at the end of the function,
+    /// so that they do not run on the other branch.
+    pub fn to_quadruples(&self) -> Vec<Quadruple> {
+        let cfg = &self.cfg;
+        let mut next_temp = self
+            .quads
+            .iter()
+            .flat_map(|q| [&q.arg1, &q.arg2, &q.result])
+            .filter_map(|operand| match operand {
+                Operand::Temp(n) => Some(n + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+
+        let mut output: Vec<Quadruple> = Vec::with_capacity(self.quads.len());
+        // new index of the first quadruple of every block
+        let mut block_start = vec![0; cfg.blocks.len()];
+        // conditional jumps sent to the copies of their edge, and where
+        // those copies start
+        let mut redirected: Vec<(usize, usize)> = Vec::new();
+
+        for function in &cfg.functions {
+            let mut edge_blocks: Vec<(usize, Vec<Quadruple>)> = Vec::new();
+            for block in &cfg.blocks[function.clone()] {
+                block_start[block.id] = output.len();
+                let last = &self.quads[block.end - 1];
+                output.extend_from_slice(&self.quads[block.start..block.end - 1]);
+                let target = last.jump_target().filter(|t| (1..=self.quads.len()).contains(t)).map(|t| cfg.block_of[t - 1]);
+                match (last.op, target) {
+                    (Opcode::Jump, Some(target)) => {
+                        output.extend(self.copies(block.id, target, &mut next_temp));
+                    }
+                    (Opcode::CondJump(_), Some(target)) if !self.phis[target].is_empty() => {
+                        edge_blocks.push((output.len(), self.copies(block.id, target, &mut next_temp)));
+                    }
+                    _ => {}
+                }
+                output.push(last.clone());
+
+                let next = block.id + 1;
+                if last.op != Opcode::Jump && block.succs.contains(&next) && next < function.end {
+                    output.extend(self.copies(block.id, next, &mut next_temp));
+                }
+            }
+
+            if edge_blocks.is_empty() {
+                continue;
+            }
+            // running off the end of the function returns 0
+            if !matches!(output.last().map(|q| q.op), Some(Opcode::Jump | Opcode::Return)) {
+                output.push(Quadruple::new(Opcode::Return, Operand::Empty, Operand::Empty, Operand::Empty));
+            }
+            for (jump, copies) in edge_blocks {
+                redirected.push((jump, output.len()));
+                // the jump keeps its old target, renumbered below
+                let target = output[jump].result.clone();
+                output.extend(copies);
+                output.push(Quadruple::new(Opcode::Jump, Operand::Empty, Operand::Empty, target));
+            }
+        }
+
+        // jump targets are always the first quadruple of a block
+        let end = output.len();
+        for quad in &mut output {
+            if quad.op.is_jump() && let Operand::Label(target) = &mut quad.result {
+                if (1..=self.quads.len()).contains(target) {
+                    *target = block_start[cfg.block_of[*target - 1]] + 1;
+                }
+                else if *target == self.quads.len() + 1 {
+                    *target = end + 1;
+                }
+            }
+        }
+        for (jump, start) in redirected {
+            output[jump].result = Operand::Label(start + 1);
+        }
+        output
+    }
+
+    /// The copies on the edge from `pred` to `succ`, in an order that reads
+    /// every source before it is overwritten.
+    fn copies(&self, pred: BlockId, succ: BlockId, next_temp: &mut usize) -> Vec<Quadruple> {
+        let copies = self.phis[succ]
+            .iter()
+            .filter_map(|phi| {
+                let (_, arg) = phi.args.iter().find(|(p, _)| *p == pred)?;
+                Some((phi.result.clone(), arg.clone()))
+            })
+            .collect();
+        sequentialize(copies, || {
+            *next_temp += 1;
+            Operand::Temp(*next_temp - 1)
+        })
+    }
+}
+
+/// Orders the copies `dest = source`, which are meant to happen at once,
+/// saving a value to a fresh temporary where the copies form a cycle.
+fn sequentialize(mut pending: Vec<(Operand, Operand)>, mut fresh: impl FnMut() -> Operand) -> Vec<Quadruple> {
+    let copy = |dest: Operand, source: Operand| Quadruple::new(Opcode::Assign, source, Operand::Empty, dest);
+    pending.retain(|(dest, source)| dest != source);
+    let mut output = Vec::new();
+    while !pending.is_empty() {
+        // a copy whose destination no other copy still reads
+        let ready = pending.iter().position(|(dest, _)| !pending.iter().any(|(_, source)| source == dest));
+        match ready {
+            Some(i) => {
+                let (dest, source) = pending.remove(i);
+                output.push(copy(dest, source));
+            }
+            None => {
+                let saved = pending[0].0.clone();
+                let temp = fresh();
+                output.push(copy(temp.clone(), saved.clone()));
+                for (_, source) in &mut pending {
+                    if *source == saved {
+                        *source = temp.clone();
+                    }
+                }
+            }
+        }
+    }
+    output
+}
+
+impl std::fmt::Display for Ssa {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for block in &self.cfg.blocks {
+            writeln!(f, "B{}:", block.id)?;
+            for phi in &self.phis[block.id] {
+                writeln!(f, "    {}", phi)?;
+            }
+            for index in block.range() {
+                writeln!(f, "    {}: {}", index + 1, self.quads[index])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::ir::{format_quadruples, parse_quadruples};
+    use crate::test_util::compile;
+
+    /// Goes into SSA and back, checking that the result of `main` is
+    /// unchanged.
+    fn round_trip(quads: &[Quadruple]) -> Vec<Quadruple> {
+        let output = Ssa::build(quads).to_quadruples();
+        assert_eq!(Interpreter::new(&output).run(), Interpreter::new(quads).run());
+        output
+    }
+
+    #[test]
+    fn test_loop() {
+        let quads = compile("int main() {
+            int i = 0;
+            int s = 0;
+            while (i < 5) { s = s + i; i = i + 1; }
+            return s;
+        }");
+        let ssa = Ssa::build(&quads);
+        assert_eq!(ssa.to_string(), "\
+B0:
+    1: (func, main, , )
+    2: (=, 0, , i#1)
+    3: (=, 0, , s#1)
+B1:
+    i#2 = phi(B0: i#1, B3: i#3)
+    s#2 = phi(B0: s#1, B3: s#3)
+    4: (j<, i#2, 5, 6)
+B2:
+    5: (j, , , 11)
+B3:
+    6: (+, s#2, i#2, t1#1)
+    7: (=, t1#1, , s#3)
+    8: (+, i#2, 1, t2#1)
+    9: (=, t2#1, , i#3)
+    10: (j, , , 4)
+B4:
+    11: (return, s#2, , )
+");
+        assert_eq!(format_quadruples(&round_trip(&quads)), "\
+1: (func, main, , )
+2: (=, 0, , i#1)
+3: (=, 0, , s#1)
+4: (=, i#1, , i#2)
+5: (=, s#1, , s#2)
+6: (j<, i#2, 5, 8)
+7: (j, , , 15)
+8: (+, s#2, i#2, t1#1)
+9: (=, t1#1, , s#3)
+10: (+, i#2, 1, t2#1)
+11: (=, t2#1, , i#3)
+12: (=, i#3, , i#2)
+13: (=, s#3, , s#2)
+14: (j, , , 6)
+15: (return, s#2, , )
+");
+    }
+
+    #[test]
+    fn test_phis_only_where_live() {
+        // `y` is assigned on both branches but dead afterwards; the else
+        // branch, B3, is renamed first as it comes first in the dominator
+        // tree
+        let quads = compile("int main() {
+            int x = 1;
+            int y = 0;
+            if (x > 0) { x = 2; y = 1; } else { x = 3; y = 2; }
+            return x;
+        }");
+        let ssa = Ssa::build(&quads);
+        let phis: Vec<String> = ssa.phis.iter().flatten().map(|phi| phi.to_string()).collect();
+        assert_eq!(phis, vec!["x#4 = phi(B2: x#3, B3: x#2)"]);
+        round_trip(&quads);
+    }
+
+    #[test]
+    fn test_conditional_jump_to_join() {
+        // the jump from 3 to 5 needs its own copy, which must not run when
+        // control falls through to 4
+        let quads = parse_quadruples("
+            (func, main, , )
+            (=, 1, , x)
+            (j<, x, 2, 5)
+            (=, 2, , x)
+            (return, x, , )
+        ").unwrap();
+        assert_eq!(format_quadruples(&round_trip(&quads)), "\
+1: (func, main, , )
+2: (=, 1, , x#1)
+3: (j<, x#1, 2, 7)
+4: (=, 2, , x#2)
+5: (=, x#2, , x#3)
+6: (return, x#3, , )
+7: (=, x#1, , x#3)
+8: (j, , , 6)
+");
+    }
+
+    #[test]
+    fn test_programs_round_trip() {
+        round_trip(&compile("
+            int fact(int n) { if (n <= 1) { return 1; } return n * fact(n - 1); }
+            int main() {
+                int i = 0;
+                int s = 0;
+                while (i < 4) {
+                    int j = 0;
+                    while (j < i) { s = s + fact(j); j = j + 1; }
+                    if (s > 10 && i != 2) { s = s - 1; } else { s = s + 2; }
+                    i = i + 1;
+                }
+                return s;
+            }
+        "));
+        let quads = round_trip(&parse_quadruples("
+            (func, main, , )
+            (=, 3, , n)
+            (=, 0, , s)
+            (j<=, n, 0, 9)
+            (+, s, n, t1)
+            (=, t1, , s)
+            (-, n, 1, n)
+            (j, , , 4)
+            (return, s, , )
+        ").unwrap());
+        // versioned names can be read back
+        assert_eq!(parse_quadruples(&format_quadruples(&quads)), Ok(quads));
+    }
+
+    #[test]
+    fn test_sequentialize() {
+        let var = |name: &str| Operand::Var(name.to_string());
+        let mut fresh = 7;
+        let copies = sequentialize(vec![(var("a"), var("b")), (var("b"), var("a")), (var("c"), var("a"))], || {
+            fresh += 1;
+            Operand::Temp(fresh - 1)
+        });
+        let text: Vec<String> = copies.iter().map(|q| q.to_string()).collect();
+        assert_eq!(text, vec!["(=, a, , c)", "(=, a, , t7)", "(=, b, , a)", "(=, t7, , b)"]);
+    }
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the unit tests.
+
+use crate::ast::Program;
+use crate::codegen::CodeGenerator;
+use crate::interpreter::Interpreter;
+use crate::ir::{format_quadruples, Quadruple};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// Parses a syntactically valid program.
+pub(crate) fn parse(input: &str) -> Program {
+    let tokens = Lexer::new(input).to_tokens().unwrap();
+    Parser::new(&tokens).parse().unwrap()
+}
+
+/// Compiles a well-formed program to quadruples.
+pub(crate) fn compile(input: &str) -> Vec<Quadruple> {
+    let mut codegen = CodeGenerator::new();
+    codegen.generate(&parse(input)).unwrap();
+    codegen.quadruples
+}
+
+/// Runs `pass` over `quads`, checking that the result of `main` is
+/// unchanged, and returns what the pass returned with the listing of the
+/// optimised quadruples. A runtime error must stay of the same kind but may
+/// be reported at a different quadruple.
+pub(crate) fn optimise<T>(mut quads: Vec<Quadruple>, pass: impl FnOnce(&mut Vec<Quadruple>) -> T) -> (T, String) {
+    let outcome = |quads: &[Quadruple]| Interpreter::new(quads).run().map_err(|e| std::mem::discriminant(&e));
+    let expected = outcome(&quads);
+    let result = pass(&mut quads);
+    assert_eq!(outcome(&quads), expected);
+    (result, format_quadruples(&quads))
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Keywords(Keyword),
@@ -6,6 +8,8 @@ pub enum Token {
     Plus,
     Minus,
     Times,
+    Divide,
+    Modulo,
     LParam,
     RParam,
     LBrace,
@@ -16,10 +20,14 @@ pub enum Token {
     Ge,
     Le,
     Ne,
+    Not,
+    And,
+    Or,
     Semicolon,
     Comma,
     Assign,
     Invalid(char),
+    UnterminatedComment,
     EOF
 }
 
@@ -57,11 +65,16 @@ impl Token {
              Token::Ge => 21,
              Token::Le => 22,
              Token::Ne => 23,
+             Token::Not => 24,
+             Token::Divide => 25,
+             Token::Modulo => 26,
+             Token::And => 27,
+             Token::Or => 28,
              Token::Semicolon => 18,
              Token::Comma => 19,
              Token::Assign => 20,
             Token::EOF => 0,
-            Token::Invalid(_) => -1
+            Token::Invalid(_) | Token::UnterminatedComment => -1
         }
     }
     pub fn content(&self) -> &str {
@@ -73,6 +86,55 @@ impl Token {
     }
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Keywords(keyword) => write!(f, "`{}`", keyword),
+            Token::Ident(s) if s.is_empty() => write!(f, "identifier"),
+            Token::Ident(s) => write!(f, "identifier `{}`", s),
+            Token::Int(s) if s.is_empty() => write!(f, "integer literal"),
+            Token::Int(s) => write!(f, "integer literal `{}`", s),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::Times => write!(f, "`*`"),
+            Token::Divide => write!(f, "`/`"),
+            Token::Modulo => write!(f, "`%`"),
+            Token::LParam => write!(f, "`(`"),
+            Token::RParam => write!(f, "`)`"),
+            Token::LBrace => write!(f, "`{{`"),
+            Token::RBrace => write!(f, "`}}`"),
+            Token::Equal => write!(f, "`==`"),
+            Token::Gt => write!(f, "`>`"),
+            Token::Lt => write!(f, "`<`"),
+            Token::Ge => write!(f, "`>=`"),
+            Token::Le => write!(f, "`<=`"),
+            Token::Ne => write!(f, "`!=`"),
+            Token::Not => write!(f, "`!`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Assign => write!(f, "`=`"),
+            Token::Invalid(c) => write!(f, "invalid character `{}`", c),
+            Token::UnterminatedComment => write!(f, "unterminated comment"),
+            Token::EOF => write!(f, "end of file"),
+        }
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Keyword::Int => "int",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::Return => "return",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 impl Keyword {
     pub fn is_keyword(s: &str) -> bool {
         const KEYWORDS: &[&str] = &[
@@ -87,6 +149,83 @@ impl Keyword {
     }
 }
 
+/// A point in the source text. `line` and `column` are 1-based and count
+/// characters, `offset` is the 0-based byte offset into the input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize, offset: usize) -> Self {
+        Position { line, column, offset }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new(1, 1, 0)
+    }
+}
+
+/// The half-open source range `[start, end)` covered by a token or AST node.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let start = if other.start.offset < self.start.offset { other.start } else { self.start };
+        let end = if other.end.offset > self.end.offset { other.end } else { self.end };
+        Span { start, end }
+    }
+}
+
+/// A token together with where it was found and the text it was read from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    pub lexeme: String,
+}
+
+impl SpannedToken {
+    pub fn new(token: Token, span: Span, lexeme: &str) -> Self {
+        SpannedToken {
+            token,
+            span,
+            lexeme: lexeme.to_string(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_display() {
+        assert_eq!(Token::Semicolon.to_string(), "`;`");
+        assert_eq!(Token::Keywords(Keyword::While).to_string(), "`while`");
+        assert_eq!(Token::Ident(String::new()).to_string(), "identifier");
+        assert_eq!(Token::Ident(String::from("x")).to_string(), "identifier `x`");
+        assert_eq!(Token::EOF.to_string(), "end of file");
+    }
+
+    #[test]
+    fn test_span_to() {
+        let a = Span::new(Position::new(1, 1, 0), Position::new(1, 4, 3));
+        let b = Span::new(Position::new(2, 3, 8), Position::new(2, 5, 10));
+        assert_eq!(a.to(b), Span::new(a.start, b.end));
+        assert_eq!(b.to(a), Span::new(a.start, b.end));
+    }
 }
@@ -12,8 +12,8 @@ fn test_example() {
     let tokens = lexer.to_tokens().unwrap();
 
     let mut out = String::new();
-    for (i, t) in tokens.into_iter().filter(|t| *t != Token::EOF).enumerate() {
-        out.push_str(&format!("({})\t{:?}\n", i + 1, t));
+    for (i, t) in tokens.into_iter().filter(|t| t.token != Token::EOF).enumerate() {
+        out.push_str(&format!("({})\t{:?}\n", i + 1, t.token));
     }
 
     let expected = "(1)\tKeywords(Int)